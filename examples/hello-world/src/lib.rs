@@ -14,18 +14,16 @@ pub fn main<R: Runtime>(application: &mut Application<R>) {
 
 #[cfg(test)]
 mod tests {
-    use rock_n_rollup::core::{Application, MockRuntime};
+    use rock_n_rollup::core::MockRuntime;
 
-    use crate::main;
+    use crate::run_with;
 
     #[test]
     fn test() {
         let mut runtime = MockRuntime::default();
         runtime.add_input(Vec::default());
 
-        let mut application = Application::new(&mut runtime);
-
-        let () = main(&mut application);
+        run_with(&mut runtime);
 
         assert_eq!(runtime.stdout(), vec!["Hello world\n"]);
     }
@@ -49,9 +49,9 @@ pub fn ticket_transition<L: Logger>(
     let source = transfer.source();
     let sender = transfer.sender();
 
-    logger.info(destination);
-    logger.info(sender);
-    logger.info(source);
+    logger.info(&destination.to_string());
+    logger.info(&sender.to_string());
+    logger.info(&source.to_string());
 }
 
 #[rock_n_rollup::main]
@@ -11,7 +11,7 @@ fn transition<R: Logger>(rt: &mut R) {
 #[rock_n_rollup::main]
 pub fn main<R: Runtime>(application: &mut Application<R>) {
     application
-        .service(TicketUpgrade::new("KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ"))
+        .service(TicketUpgrade::new("KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ").unwrap())
         .register(transition)
         .run();
 }
@@ -0,0 +1,268 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    spanned::Spanned, Attribute, Data, DataEnum, DeriveInput, Error, Fields, LitInt, LitStr, Result,
+};
+
+/// Codec used to decode the fields of a message
+enum Codec {
+    Bincode,
+    Json,
+    Tezos,
+}
+
+impl Codec {
+    fn path(&self) -> TokenStream {
+        match self {
+            Codec::Bincode => quote! { rock_n_rollup::encoding::codec::Bincode },
+            Codec::Json => quote! { rock_n_rollup::encoding::codec::Json },
+            Codec::Tezos => quote! { rock_n_rollup::encoding::codec::TezosBinary },
+        }
+    }
+}
+
+/// Attributes that can be put on the type deriving FromExternal
+///
+/// `#[external(codec = "json", version = 2, tag = 0x01)]`
+struct ContainerAttributes {
+    codec: Codec,
+    version: Option<u8>,
+    tag: Option<u8>,
+}
+
+/// Attributes that can be put on a variant of an enum
+///
+/// `#[external(tag = 0x01, since = 2)]`
+#[derive(Default)]
+struct VariantAttributes {
+    tag: Option<u8>,
+    since: Option<u8>,
+}
+
+fn parse_container_attributes(attrs: &[Attribute]) -> Result<ContainerAttributes> {
+    let mut attributes = ContainerAttributes {
+        codec: Codec::Bincode,
+        version: None,
+        tag: None,
+    };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("external")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("codec") {
+                let codec: LitStr = meta.value()?.parse()?;
+                attributes.codec = match codec.value().as_str() {
+                    "bincode" => Codec::Bincode,
+                    "json" => Codec::Json,
+                    "tezos" => Codec::Tezos,
+                    _ => {
+                        return Err(Error::new(
+                            codec.span(),
+                            "unknown codec, expected \"bincode\", \"json\" or \"tezos\"",
+                        ))
+                    }
+                };
+                Ok(())
+            } else if meta.path.is_ident("version") {
+                let version: LitInt = meta.value()?.parse()?;
+                attributes.version = Some(version.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("tag") {
+                let tag: LitInt = meta.value()?.parse()?;
+                attributes.tag = Some(tag.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown attribute, expected `codec`, `version` or `tag`"))
+            }
+        })?;
+    }
+    Ok(attributes)
+}
+
+fn parse_variant_attributes(attrs: &[Attribute]) -> Result<VariantAttributes> {
+    let mut attributes = VariantAttributes::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("external")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                let tag: LitInt = meta.value()?.parse()?;
+                attributes.tag = Some(tag.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("since") {
+                let since: LitInt = meta.value()?.parse()?;
+                attributes.since = Some(since.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown attribute, expected `tag` or `since`"))
+            }
+        })?;
+    }
+    Ok(attributes)
+}
+
+/// Generates the expression that decodes the given fields from the `rest` slice
+///
+/// A variant with several fields is decoded as a tuple of all its fields
+fn decode_fields(constructor: TokenStream, fields: &Fields, codec: &Codec) -> Result<TokenStream> {
+    let codec_path = codec.path();
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let bindings = (0..types.len())
+        .map(|i| format_ident!("field_{}", i))
+        .collect::<Vec<_>>();
+
+    if types.len() > 1 {
+        if let Codec::Tezos = codec {
+            return Err(Error::new(
+                fields.span(),
+                "the tezos codec decodes at most one field per variant",
+            ));
+        }
+    }
+
+    let decode = match types.len() {
+        0 => {
+            return Ok(quote! {
+                match rest {
+                    [] => Ok(#constructor),
+                    _ => Err(()),
+                }
+            })
+        }
+        1 => quote! {
+            let #(#bindings)* = <#codec_path as rock_n_rollup::encoding::codec::Codec<#(#types)*>>::decode(rest)?;
+        },
+        _ => quote! {
+            let (#(#bindings),*) = <#codec_path as rock_n_rollup::encoding::codec::Codec<(#(#types),*)>>::decode(rest)?;
+        },
+    };
+
+    let construct = match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote! { #constructor { #(#names: #bindings),* } }
+        }
+        _ => quote! { #constructor ( #(#bindings),* ) },
+    };
+
+    Ok(quote! {
+        {
+            #decode
+            Ok(#construct)
+        }
+    })
+}
+
+fn derive_enum(data: &DataEnum, attributes: &ContainerAttributes) -> Result<TokenStream> {
+    if attributes.tag.is_some() {
+        return Err(Error::new(
+            data.enum_token.span(),
+            "`tag` should be put on the variants of an enum",
+        ));
+    }
+
+    let mut tags = Vec::<u8>::default();
+    let mut arms = Vec::default();
+    for (index, variant) in data.variants.iter().enumerate() {
+        let variant_attributes = parse_variant_attributes(&variant.attrs)?;
+        let tag = match variant_attributes.tag {
+            Some(tag) => tag,
+            None => u8::try_from(index)
+                .map_err(|_| Error::new(variant.span(), "an enum can have up to 256 variants"))?,
+        };
+        if tags.contains(&tag) {
+            return Err(Error::new(
+                variant.span(),
+                format!("the tag {} is already used by another variant", tag),
+            ));
+        }
+        tags.push(tag);
+
+        let since = match (variant_attributes.since, attributes.version) {
+            (None, _) => quote! {},
+            (Some(since), Some(_)) => quote! {
+                if version < #since {
+                    return Err(());
+                }
+            },
+            (Some(_), None) => {
+                return Err(Error::new(
+                    variant.span(),
+                    "`since` requires a `version` on the enum",
+                ))
+            }
+        };
+
+        let ident = &variant.ident;
+        let decode = decode_fields(quote! { Self::#ident }, &variant.fields, &attributes.codec)?;
+        arms.push(quote! {
+            [#tag, rest @ ..] => {
+                #since
+                #decode
+            }
+        });
+    }
+
+    Ok(quote! {
+        match bytes {
+            #(#arms)*
+            _ => Err(()),
+        }
+    })
+}
+
+fn derive_struct(attributes: &ContainerAttributes) -> TokenStream {
+    let codec_path = attributes.codec.path();
+    let decode = quote! {
+        <#codec_path as rock_n_rollup::encoding::codec::Codec<Self>>::decode(rest)
+    };
+    match attributes.tag {
+        None => quote! {
+            let rest = bytes;
+            #decode
+        },
+        Some(tag) => quote! {
+            match bytes {
+                [#tag, rest @ ..] => #decode,
+                _ => Err(()),
+            }
+        },
+    }
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let attributes = parse_container_attributes(&input.attrs)?;
+
+    let body = match &input.data {
+        Data::Enum(data) => derive_enum(data, &attributes)?,
+        Data::Struct(_) => derive_struct(&attributes),
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "FromExternal cannot be derived for unions",
+            ))
+        }
+    };
+
+    // The version byte is read before the tag
+    let version = match attributes.version {
+        None => quote! {
+            let bytes = input.as_slice();
+        },
+        Some(version) => quote! {
+            let (version, bytes) = match input.as_slice() {
+                [version, bytes @ ..] if *version >= 1 && *version <= #version => (*version, bytes),
+                _ => return Err(()),
+            };
+        },
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics rock_n_rollup::services::external::FromExternal for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn from_external(input: Vec<u8>) -> Result<Self, ()> {
+                #version
+                #body
+            }
+        }
+    })
+}
@@ -1,26 +1,159 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use syn::{parse_macro_input, parse_quote, DeriveInput, ItemFn, ItemImpl, Path, Type};
 
+mod external;
+mod michelson;
+mod service;
+
+/// Defines the entry point of the kernel
+///
+/// The generated entry point installs a panic hook that reports the panics of the kernel.
+///
+/// The macro accepts the following arguments:
+/// - `runtime = MyRuntime`: the runtime used by the kernel, it has to implement Default
+/// - `setup = init`: a function called with the runtime before the application is created
+/// - `skip_crashed_inputs`: the input that made the kernel panic during the previous run
+/// is skipped instead of being processed again
+///
+/// A `run_with` function is also generated for the tests,
+/// it runs the kernel with the same setup on the given runtime.
 #[proc_macro_attribute]
-pub fn main(_: TokenStream, input: TokenStream) -> TokenStream {
+pub fn main(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut runtime: Option<Type> = None;
+    let mut setup: Option<Path> = None;
+    let mut skip_crashed_inputs = false;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("runtime") {
+            runtime = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("setup") {
+            setup = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("skip_crashed_inputs") {
+            skip_crashed_inputs = true;
+            Ok(())
+        } else {
+            Err(meta
+                .error("unknown argument, expected `runtime`, `setup` or `skip_crashed_inputs`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
     // Parse the input tokens into a syntax tree representing a function
     let input_fn = parse_macro_input!(input as ItemFn);
 
     // Extract the name of the input function
     let fn_name = input_fn.sig.ident.clone();
 
+    let runtime = runtime.unwrap_or_else(|| parse_quote! { rock_n_rollup::core::KernelRuntime });
+
+    let setup = match setup {
+        Some(setup) => quote! { #setup(&mut *runtime); },
+        None => quote! {},
+    };
+
+    let skip_crashed_inputs = match skip_crashed_inputs {
+        true => quote! { app.skip_crashed_inputs(); },
+        false => quote! {},
+    };
+
+    // The same wiring is used by the kernel and by the tests
+    let wiring = quote! {
+        #setup
+        let mut app = rock_n_rollup::core::Application::new(runtime);
+        #skip_crashed_inputs
+        #fn_name(&mut app);
+    };
+
+    // A generic entry point can be tested with any runtime
+    let run_with = match input_fn.sig.generics.params.is_empty() {
+        true => quote! {
+            pub fn run_with(runtime: &mut #runtime) {
+                #wiring
+            }
+        },
+        false => quote! {
+            pub fn run_with<R: rock_n_rollup::core::Runtime>(runtime: &mut R) {
+                #wiring
+            }
+        },
+    };
+
     let output = quote! {
         #[export_name = "kernel_run"]
         pub extern "C" fn kernel_run() {
-            let mut runtime = rock_n_rollup::core::KernelRuntime::default();
-            let mut app = rock_n_rollup::core::Application::new(&mut runtime);
-            #fn_name(&mut app);
+            rock_n_rollup::core::install_panic_hook();
+            let mut runtime = <#runtime as Default>::default();
+            let runtime = &mut runtime;
+            #wiring
         }
 
+        #[cfg(test)]
+        #run_with
+
         #input_fn
     };
 
     // Return the generated tokens
     output.into()
 }
+
+/// Derives the FromExternal trait
+///
+/// Each variant of an enum is identified by a one byte tag, by default its index.
+/// The fields of the variant are decoded with the codec of the enum (bincode by default).
+///
+/// A struct is decoded as a whole by the codec, optionally after a tag.
+#[proc_macro_derive(FromExternal, attributes(external))]
+pub fn from_external(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    external::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Derives the Michelson encoding of a struct or an enum
+///
+/// The fields of a struct are encoded as a right comb of pairs, a struct without fields as `Unit`.
+/// The variants of an enum are encoded as a right comb of ors, each variant encoding its fields like a struct.
+///
+/// The Michelson type of the value is annotated with the names of the fields and the variants in snake case,
+/// they can be renamed with `#[michelson(annot = "name")]`.
+#[proc_macro_derive(Michelson, attributes(michelson))]
+pub fn michelson(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    michelson::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Declares a service from an impl block
+///
+/// Functions annotated with `#[transition]` are registered as transitions of the service,
+/// and the ones annotated with `#[guard]` as its guards.
+/// A guard can take a reference to the state as third parameter.
+///
+/// The state of the service, the type of the impl block, has to implement Clone,
+/// it can then be extracted by the transitions.
+#[proc_macro_attribute]
+pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut arguments = service::ServiceArguments { payload: None };
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("payload") {
+            arguments.payload = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown argument, expected `payload`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let input = parse_macro_input!(input as ItemImpl);
+
+    service::expand(arguments, input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
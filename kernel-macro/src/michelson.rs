@@ -0,0 +1,307 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    spanned::Spanned, Attribute, Data, DataEnum, DeriveInput, Error, Fields, Ident, LitStr, Result,
+};
+
+/// Attributes that can be put on a field or a variant
+///
+/// `#[michelson(annot = "amount")]`
+#[derive(Default)]
+struct Attributes {
+    annot: Option<String>,
+}
+
+fn parse_attributes(attrs: &[Attribute]) -> Result<Attributes> {
+    let mut attributes = Attributes::default();
+    for attr in attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("michelson"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("annot") {
+                let annot: LitStr = meta.value()?.parse()?;
+                attributes.annot = Some(annot.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown attribute, expected `annot`"))
+            }
+        })?;
+    }
+    Ok(attributes)
+}
+
+/// Path of the module of the helpers used by the generated code
+fn helpers() -> TokenStream {
+    quote! { rock_n_rollup::encoding::michelson }
+}
+
+/// Converts the name of a variant to the snake case of the entrypoints
+fn snake_case(ident: &Ident) -> String {
+    let mut name = String::default();
+    for (index, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() {
+            if index > 0 {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+fn bindings(fields: &Fields) -> Vec<Ident> {
+    (0..fields.len())
+        .map(|i| format_ident!("field_{}", i))
+        .collect()
+}
+
+/// Generates the pattern destructuring, or the expression constructing, the fields
+fn fields_pattern(constructor: TokenStream, fields: &Fields) -> TokenStream {
+    let bindings = bindings(fields);
+    match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote! { #constructor { #(#names: #bindings),* } }
+        }
+        Fields::Unnamed(_) => quote! { #constructor ( #(#bindings),* ) },
+        Fields::Unit => constructor,
+    }
+}
+
+/// Generates the statements writing the fields as a right comb of pairs
+///
+/// The fields are bound to `field_<index>`, no field is written as `Unit`
+fn write_fields(fields: &Fields) -> TokenStream {
+    let m = helpers();
+    let bindings = bindings(fields);
+    if bindings.is_empty() {
+        return quote! { #m::bin_write_unit(output); };
+    }
+
+    let last = bindings.len() - 1;
+    let writes = bindings.iter().enumerate().map(|(index, binding)| {
+        let pair = match index < last {
+            true => quote! { #m::bin_write_pair(output); },
+            false => quote! {},
+        };
+        quote! {
+            #pair
+            #m::BinWriter::bin_write(#binding, output)?;
+        }
+    });
+    quote! { #(#writes)* }
+}
+
+/// Generates the statements reading the fields from a right comb of pairs, binding them to `field_<index>`
+fn read_fields(fields: &Fields) -> TokenStream {
+    let m = helpers();
+    let bindings = bindings(fields);
+    if bindings.is_empty() {
+        return quote! { let (input, ()) = #m::nom_read_unit(input)?; };
+    }
+
+    let last = bindings.len() - 1;
+    let reads = fields
+        .iter()
+        .zip(bindings.iter())
+        .enumerate()
+        .map(|(index, (field, binding))| {
+            let ty = &field.ty;
+            let pair = match index < last {
+                true => quote! { let (input, ()) = #m::nom_read_pair(input)?; },
+                false => quote! {},
+            };
+            quote! {
+                #pair
+                let (input, #binding) = <#ty as #m::NomReader>::nom_read(input)?;
+            }
+        });
+    quote! { #(#reads)* }
+}
+
+/// Generates the expression of the Michelson type of the fields, with their annotations
+///
+/// The named fields are annotated with their name by default
+fn fields_type(fields: &Fields) -> Result<TokenStream> {
+    let m = helpers();
+    let mut types = Vec::default();
+    for field in fields.iter() {
+        let ty = &field.ty;
+        let annot = match (parse_attributes(&field.attrs)?.annot, &field.ident) {
+            (Some(annot), _) => quote! { Some(#annot) },
+            (None, Some(ident)) => {
+                let annot = ident.to_string();
+                quote! { Some(#annot) }
+            }
+            (None, None) => quote! { None },
+        };
+        types.push(quote! {
+            #m::annotate(&<#ty as #m::MichelsonType>::michelson_type(), #annot)
+        });
+    }
+
+    Ok(match types.len() {
+        0 => quote! { String::from("unit") },
+        1 => quote! { #(#types)* },
+        _ => quote! { #m::pair(&[#(#types),*]) },
+    })
+}
+
+/// Generates the statements reading the variants, starting from the given one, from a right comb of ors
+fn read_variants(data: &DataEnum, index: usize) -> TokenStream {
+    let m = helpers();
+    let variant = &data.variants[index];
+    let ident = &variant.ident;
+    let read = read_fields(&variant.fields);
+    let construct = fields_pattern(quote! { Self::#ident }, &variant.fields);
+    let read = quote! {
+        #read
+        Ok((input, #construct))
+    };
+
+    match index + 1 < data.variants.len() {
+        false => read,
+        true => {
+            let right = read_variants(data, index + 1);
+            quote! {
+                let (input, left) = #m::nom_read_or(input)?;
+                if left {
+                    #read
+                } else {
+                    #right
+                }
+            }
+        }
+    }
+}
+
+struct Body {
+    write: TokenStream,
+    read: TokenStream,
+    michelson_type: TokenStream,
+}
+
+fn derive_enum(data: &DataEnum) -> Result<Body> {
+    let m = helpers();
+    if data.variants.is_empty() {
+        return Err(Error::new(
+            data.enum_token.span(),
+            "Michelson cannot be derived for enums without variants",
+        ));
+    }
+
+    let last = data.variants.len() - 1;
+    let mut arms = Vec::default();
+    let mut types = Vec::default();
+    for (index, variant) in data.variants.iter().enumerate() {
+        let ident = &variant.ident;
+        let pattern = fields_pattern(quote! { Self::#ident }, &variant.fields);
+        let rights = (0..index).map(|_| quote! { #m::bin_write_right(output); });
+        let left = match index < last {
+            true => quote! { #m::bin_write_left(output); },
+            false => quote! {},
+        };
+        let write = write_fields(&variant.fields);
+        arms.push(quote! {
+            #pattern => {
+                #(#rights)*
+                #left
+                #write
+            }
+        });
+
+        let annot = parse_attributes(&variant.attrs)?
+            .annot
+            .unwrap_or_else(|| snake_case(ident));
+        let ty = fields_type(&variant.fields)?;
+        types.push(quote! { #m::annotate(&#ty, Some(#annot)) });
+    }
+
+    // The variants are nested from the last one
+    let michelson_type = types
+        .into_iter()
+        .rev()
+        .reduce(|right, left| quote! { #m::or(&#left, &#right) })
+        .unwrap();
+
+    Ok(Body {
+        write: quote! {
+            match self {
+                #(#arms)*
+            }
+        },
+        read: read_variants(data, 0),
+        michelson_type,
+    })
+}
+
+fn derive_struct(fields: &Fields) -> Result<Body> {
+    let pattern = fields_pattern(quote! { Self }, fields);
+    let write = write_fields(fields);
+    let read = read_fields(fields);
+    Ok(Body {
+        write: quote! {
+            let #pattern = self;
+            #write
+        },
+        read: quote! {
+            #read
+            Ok((input, #pattern))
+        },
+        michelson_type: fields_type(fields)?,
+    })
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let body = match &input.data {
+        Data::Enum(data) => derive_enum(data)?,
+        Data::Struct(data) => derive_struct(&data.fields)?,
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "Michelson cannot be derived for unions",
+            ))
+        }
+    };
+    let Body {
+        write,
+        read,
+        michelson_type,
+    } = body;
+
+    let m = helpers();
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #m::HasEncoding for #name #ty_generics #where_clause {
+            fn encoding() -> #m::Encoding {
+                #m::Encoding::Custom
+            }
+        }
+
+        impl #impl_generics #m::NomReader for #name #ty_generics #where_clause {
+            fn nom_read(input: &[u8]) -> #m::NomResult<'_, Self> {
+                #read
+            }
+        }
+
+        impl #impl_generics #m::BinWriter for #name #ty_generics #where_clause {
+            fn bin_write(&self, output: &mut Vec<u8>) -> #m::BinResult {
+                #write
+                Ok(())
+            }
+        }
+
+        impl #impl_generics #m::Michelson for #name #ty_generics #where_clause {}
+
+        impl #impl_generics #m::MichelsonType for #name #ty_generics #where_clause {
+            fn michelson_type() -> String {
+                #michelson_type
+            }
+        }
+    })
+}
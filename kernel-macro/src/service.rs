@@ -0,0 +1,82 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Ident, ImplItem, ItemImpl, Result, Type};
+
+/// Arguments of the service attribute
+///
+/// `#[rock_n_rollup::service(payload = RawExternalOperation)]`
+pub struct ServiceArguments {
+    pub payload: Option<Type>,
+}
+
+/// Removes the given attribute from the function, returns true if it was present
+fn take_attribute(item: &mut ImplItem, name: &str) -> bool {
+    match item {
+        ImplItem::Fn(function) => {
+            let size = function.attrs.len();
+            function.attrs.retain(|attr| !attr.path().is_ident(name));
+            size != function.attrs.len()
+        }
+        _ => false,
+    }
+}
+
+pub fn expand(arguments: ServiceArguments, mut input: ItemImpl) -> Result<TokenStream> {
+    let payload = arguments
+        .payload
+        .unwrap_or_else(|| parse_quote! { Vec<u8> });
+
+    let mut guards = Vec::<Ident>::default();
+    let mut state_guards = Vec::<Ident>::default();
+    let mut transitions = Vec::<Ident>::default();
+
+    for item in input.items.iter_mut() {
+        let is_guard = take_attribute(item, "guard");
+        let is_transition = take_attribute(item, "transition");
+        if let ImplItem::Fn(function) = item {
+            let ident = function.sig.ident.clone();
+            // A guard taking a third parameter reads the state of the service
+            if is_guard && function.sig.inputs.len() == 3 {
+                state_guards.push(ident.clone());
+            } else if is_guard {
+                guards.push(ident.clone());
+            }
+            if is_transition {
+                transitions.push(ident);
+            }
+        }
+    }
+
+    let self_ty = &input.self_ty;
+    let (impl_generics, _, where_clause) = input.generics.split_for_impl();
+
+    let mut service_generics = input.generics.clone();
+    service_generics
+        .params
+        .push(parse_quote! { R: rock_n_rollup::core::Runtime });
+    let (service_impl_generics, _, _) = service_generics.split_for_impl();
+
+    Ok(quote! {
+        #input
+
+        impl #impl_generics rock_n_rollup::core::FromInput<#payload, #self_ty> for #self_ty #where_clause {
+            fn from_input<R: rock_n_rollup::core::Runtime>(
+                _: &mut R,
+                _: &rock_n_rollup::core::Input<#payload>,
+                state: &#self_ty,
+            ) -> Result<Self, ()> {
+                Ok(state.clone())
+            }
+        }
+
+        impl #service_impl_generics rock_n_rollup::core::IntoService<R, #payload, #self_ty> for #self_ty #where_clause {
+            fn into_service(self) -> rock_n_rollup::core::Service<R, #payload, Self> {
+                let mut service = rock_n_rollup::core::Service::<R, #payload, Self>::new(self);
+                #(service.add_guard(Self::#guards);)*
+                #(service.add_state_guard(Self::#state_guards);)*
+                #(service.register(Self::#transitions);)*
+                service
+            }
+        }
+    })
+}
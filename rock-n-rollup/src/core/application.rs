@@ -1,4 +1,8 @@
+use crate::plugins::events::Events;
+use crate::plugins::logger::Logger;
+
 use super::{
+    crash,
     service::{Input, IntoTransition, Runnable, Service},
     FromRawInput, IntoService, Runtime,
 };
@@ -10,6 +14,7 @@ where
     runtime: &'a mut R,
     services: Vec<Box<dyn Runnable<R>>>,
     base: Service<R, Vec<u8>, ()>,
+    skip_crashed_inputs: bool,
 }
 
 impl<'a, R: Runtime + 'static> Application<'a, R> {
@@ -38,6 +43,14 @@ impl<'a, R: Runtime + 'static> Application<'a, R> {
         self
     }
 
+    /// Skip the input that made the kernel panic during the previous run
+    ///
+    /// Otherwise the kernel will try to process it again
+    pub fn skip_crashed_inputs(&mut self) -> &mut Self {
+        self.skip_crashed_inputs = true;
+        self
+    }
+
     pub fn run(&mut self) {
         let mut is_running = true;
         while is_running {
@@ -45,15 +58,34 @@ impl<'a, R: Runtime + 'static> Application<'a, R> {
             match input {
                 None => is_running = false,
                 Some(input) => {
+                    if self.skip_crashed_inputs && crash::is_crashed_input(self.runtime, &input) {
+                        self.runtime.warn(&format!(
+                            "skipping the input {} at level {}",
+                            input.id, input.level
+                        ));
+                        let _ = self.runtime.store_delete(crash::CRASHED_INPUT_PATH);
+                        continue;
+                    }
+                    crash::set_current_input(&input);
+
                     self.base.run(self.runtime, input.clone());
 
                     self.services.iter_mut().for_each(|service| {
                         println!("service 1");
                         service.run(self.runtime, input.clone())
                     });
+
+                    self.base.finish(self.runtime, &input);
+                    self.services
+                        .iter_mut()
+                        .for_each(|service| service.finish(self.runtime, &input));
+
+                    // The events that have not been written in a receipt are dropped
+                    let _ = self.runtime.take_events();
                 }
             }
         }
+        crash::clear_current_input();
     }
 }
 
@@ -66,6 +98,7 @@ where
             runtime,
             services: Vec::default(),
             base: Service::<R, Vec<u8>, ()>::new(()),
+            skip_crashed_inputs: false,
         }
     }
 }
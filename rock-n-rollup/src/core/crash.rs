@@ -0,0 +1,149 @@
+use std::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::{KernelRuntime, RawInput, Runtime};
+
+/// Path of the durable storage where the input that made the kernel panic is saved
+pub const CRASHED_INPUT_PATH: &str = "/crash/input";
+
+const NO_INPUT: u64 = u64::MAX;
+
+/// Level and id of the input being processed by the application
+static CURRENT_INPUT: AtomicU64 = AtomicU64::new(NO_INPUT);
+
+fn encode(level: u32, id: u32) -> u64 {
+    (u64::from(level) << 32) | u64::from(id)
+}
+
+pub(crate) fn set_current_input(input: &RawInput) {
+    CURRENT_INPUT.store(encode(input.level, input.id), Ordering::SeqCst);
+}
+
+pub(crate) fn clear_current_input() {
+    CURRENT_INPUT.store(NO_INPUT, Ordering::SeqCst);
+}
+
+fn current_input() -> Option<(u32, u32)> {
+    match CURRENT_INPUT.load(Ordering::SeqCst) {
+        NO_INPUT => None,
+        input => Some(((input >> 32) as u32, input as u32)),
+    }
+}
+
+/// Returns true if the given input is the one that made the kernel panic
+pub(crate) fn is_crashed_input<R: Runtime>(runtime: &mut R, input: &RawInput) -> bool {
+    if !runtime.store_is_present(CRASHED_INPUT_PATH) {
+        return false;
+    }
+    match runtime.store_read(CRASHED_INPUT_PATH, 0, 8) {
+        Some(bytes) => bytes == encode(input.level, input.id).to_be_bytes(),
+        None => false,
+    }
+}
+
+/// Writes the panic to the debug output and saves the failing input in the durable storage
+fn record_crash<R: Runtime>(
+    runtime: &mut R,
+    input: Option<(u32, u32)>,
+    location: &str,
+    message: &str,
+) {
+    runtime.write_debug(&format!("[PANIC] {}: {}\n", location, message));
+
+    if let Some((level, id)) = input {
+        runtime.write_debug(&format!(
+            "[PANIC] while processing the input {} at level {}\n",
+            id, level
+        ));
+        let _ = runtime.store_delete(CRASHED_INPUT_PATH);
+        let _ = runtime.store_write(CRASHED_INPUT_PATH, &encode(level, id).to_be_bytes(), 0);
+    }
+}
+
+fn panic_message(info: &PanicInfo) -> String {
+    let payload = info.payload();
+    match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string(),
+    }
+}
+
+/// Installs a panic hook reporting the panics of the kernel
+///
+/// The location and the message of the panic are written to the debug output,
+/// and the input being processed is saved under CRASHED_INPUT_PATH
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}", location.file(), location.line()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        let message = panic_message(info);
+
+        let mut runtime = KernelRuntime::default();
+        record_crash(&mut runtime, current_input(), &location, &message);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{Application, MockRuntime, Runtime};
+
+    use super::{record_crash, CRASHED_INPUT_PATH};
+
+    fn transition<R: Runtime>(rt: &mut R) {
+        rt.write_debug("processed");
+    }
+
+    #[test]
+    fn test_record_crash() {
+        let mut runtime = MockRuntime::default();
+
+        record_crash(&mut runtime, Some((3, 2)), "src/lib.rs:1", "oops");
+
+        assert_eq!(
+            runtime.stdout(),
+            vec![
+                "[PANIC] src/lib.rs:1: oops\n",
+                "[PANIC] while processing the input 2 at level 3\n"
+            ]
+        );
+        let saved = runtime.store_read(CRASHED_INPUT_PATH, 0, 8).unwrap();
+        assert_eq!(saved, vec![0, 0, 0, 3, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn test_skip_crashed_input() {
+        let mut runtime = MockRuntime::default();
+        runtime.add_input(Vec::default()).add_input(Vec::default());
+        record_crash(&mut runtime, Some((0, 1)), "src/lib.rs:1", "oops");
+
+        Application::new(&mut runtime)
+            .skip_crashed_inputs()
+            .register(transition)
+            .run();
+
+        assert_eq!(
+            runtime.stdout()[2..],
+            vec!["[WARN] skipping the input 1 at level 0\n", "processed"]
+        );
+        assert!(!runtime.store_is_present(CRASHED_INPUT_PATH));
+    }
+
+    #[test]
+    fn test_do_not_skip_by_default() {
+        let mut runtime = MockRuntime::default();
+        runtime.add_input(Vec::default()).add_input(Vec::default());
+        record_crash(&mut runtime, Some((0, 1)), "src/lib.rs:1", "oops");
+
+        Application::new(&mut runtime).register(transition).run();
+
+        assert_eq!(runtime.stdout()[2..], vec!["processed", "processed"]);
+    }
+}
@@ -1,21 +1,30 @@
 mod application;
 mod constants;
+mod crash;
 mod runtime;
 mod service;
 
 pub mod michelson {
+    pub use crate::encoding::michelson::{Michelson, MichelsonType};
     pub use tezos_smart_rollup_encoding::michelson::*;
 }
 
 pub use application::Application;
 pub use constants::PREIMAGE_HASH_SIZE;
+pub use crash::install_panic_hook;
+pub use crash::CRASHED_INPUT_PATH;
 pub use runtime::KernelRuntime;
 pub use runtime::MockRuntime;
 pub use runtime::RawInput;
 pub use runtime::Runtime;
+pub use runtime::MAX_FILE_CHUNK_SIZE;
+pub use runtime::MAX_OUTPUT_SIZE;
+pub use runtime::MOCK_ROLLUP_ADDRESS;
 pub use service::FromInput;
 pub use service::FromRawInput;
 pub use service::Input;
 pub use service::IntoService;
+pub use service::Outcome;
 pub use service::Runnable;
 pub use service::Service;
+pub use service::TransitionResult;
@@ -1,9 +1,22 @@
-use std::{collections::HashMap, println};
+use std::collections::HashMap;
 
 use super::constants::PREIMAGE_HASH_SIZE;
+use crate::encoding::{address::SmartRollupAddress, b58::B58Check};
 
 pub const MAX_MESSAGE_SIZE: usize = 4096;
 
+/// Maximum size of a message written to the outbox
+pub const MAX_OUTPUT_SIZE: usize = 4096;
+
+/// Maximum size of the data written to the durable storage at once
+pub const MAX_FILE_CHUNK_SIZE: usize = 2048;
+
+/// Size of the metadata of the rollup: its address followed by its origination level
+const METADATA_SIZE: usize = 24;
+
+/// Address of the rollup run by the MockRuntime
+pub const MOCK_ROLLUP_ADDRESS: &str = "sr1UNDWPUYVeomgG15wn5jSw689EJ4RNnVQa";
+
 #[derive(Clone)]
 pub struct RawInput {
     pub level: u32,
@@ -72,6 +85,12 @@ extern "C" {
         dst_path: *const u8,
         dst_path_len: usize,
     ) -> i32;
+
+    /// Returns the number of bytes written at `dst`, or an error code.
+    pub fn reveal_metadata(dst: *mut u8, max_bytes: usize) -> i32;
+
+    /// Returns 0 in case of success, or an error code.
+    pub fn write_output(src: *const u8, num_bytes: usize) -> i32;
 }
 
 pub trait Runtime: 'static {
@@ -101,10 +120,43 @@ pub trait Runtime: 'static {
 
     /// Move the data to another path
     fn store_move(&mut self, from: &str, to: &str) -> Result<(), ()>;
+
+    /// Address of the rollup running the kernel
+    ///
+    /// By default the address is revealed by the host at each call
+    fn rollup_address(&mut self) -> Result<SmartRollupAddress, ()> {
+        reveal_rollup_address()
+    }
+
+    /// Writes a message to the outbox of the current level
+    ///
+    /// Returns an error if the message is too large or the outbox is full
+    fn write_output(&mut self, output: &[u8]) -> Result<(), ()> {
+        let res = unsafe { write_output(output.as_ptr(), output.len()) };
+        match res {
+            0 => Ok(()),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Reveals the metadata of the rollup, and extracts its address
+fn reveal_rollup_address() -> Result<SmartRollupAddress, ()> {
+    let mut metadata = [0u8; METADATA_SIZE];
+    let size = unsafe { reveal_metadata(metadata.as_mut_ptr(), METADATA_SIZE) };
+    if usize::try_from(size) != Ok(METADATA_SIZE) {
+        return Err(());
+    }
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&metadata[..20]);
+    Ok(SmartRollupAddress::from(address))
 }
 
 #[derive(Default)]
-pub struct KernelRuntime {}
+pub struct KernelRuntime {
+    /// The address of the rollup does not change, it is revealed once
+    rollup_address: Option<SmartRollupAddress>,
+}
 
 impl Runtime for KernelRuntime {
     fn write_debug(&mut self, msg: &str) {
@@ -212,12 +264,25 @@ impl Runtime for KernelRuntime {
             _ => Err(()),
         }
     }
+
+    fn rollup_address(&mut self) -> Result<SmartRollupAddress, ()> {
+        match &self.rollup_address {
+            Some(address) => Ok(address.clone()),
+            None => {
+                let address = reveal_rollup_address()?;
+                self.rollup_address = Some(address.clone());
+                Ok(address)
+            }
+        }
+    }
 }
 
 pub struct MockRuntime {
     stdout: Vec<String>,
     inputs: Vec<RawInput>,
     storage: HashMap<String, Vec<u8>>,
+    rollup_address: SmartRollupAddress,
+    outputs: Vec<Vec<u8>>,
 }
 
 impl Default for MockRuntime {
@@ -226,11 +291,19 @@ impl Default for MockRuntime {
             stdout: Vec::default(),
             inputs: Vec::default(),
             storage: HashMap::default(),
+            rollup_address: SmartRollupAddress::from_b58check(MOCK_ROLLUP_ADDRESS).unwrap(),
+            outputs: Vec::default(),
         }
     }
 }
 
 impl MockRuntime {
+    /// Changes the address of the rollup, by default MOCK_ROLLUP_ADDRESS
+    pub fn set_rollup_address(&mut self, address: SmartRollupAddress) -> &mut Self {
+        self.rollup_address = address;
+        self
+    }
+
     pub fn stdout(&self) -> Vec<&str> {
         self.stdout
             .iter()
@@ -238,8 +311,17 @@ impl MockRuntime {
             .collect::<Vec<&str>>()
     }
 
+    /// Messages written to the outbox
+    pub fn outputs(&self) -> &[Vec<u8>] {
+        &self.outputs
+    }
+
     pub fn add_input(&mut self, input: Vec<u8>) -> &mut Self {
-        let level = 0;
+        self.add_input_at_level(0, input)
+    }
+
+    /// Adds an input sent at the given level
+    pub fn add_input_at_level(&mut self, level: u32, input: Vec<u8>) -> &mut Self {
         let id = self.inputs.len();
         let msg = RawInput {
             level,
@@ -260,45 +342,43 @@ impl Runtime for MockRuntime {
         self.inputs.pop()
     }
 
-    fn store_is_present(&mut self, _path: &str) -> bool {
-        todo!()
+    fn store_is_present(&mut self, path: &str) -> bool {
+        let prefix = format!("{}/", path);
+        self.storage
+            .keys()
+            .any(|key| key == path || key.starts_with(&prefix))
     }
 
-    fn store_delete(&mut self, _path: &str) -> Result<(), ()> {
-        todo!()
+    fn store_delete(&mut self, path: &str) -> Result<(), ()> {
+        let prefix = format!("{}/", path);
+        self.storage
+            .retain(|key, _| key != path && !key.starts_with(&prefix));
+        Ok(())
     }
 
     fn store_read(&mut self, path: &str, offset: usize, size: usize) -> Option<Vec<u8>> {
-        let bytes = self.storage.get(path).cloned()?;
-        if offset + size <= bytes.len() {
-            let data = &bytes[offset..offset + size].to_vec();
-            Some(data.clone())
-        } else {
-            todo!()
+        let bytes = self.storage.get(path)?;
+        if offset > bytes.len() {
+            return None;
         }
+        // Like the kernel, reads at most the available bytes
+        let end = usize::min(offset + size, bytes.len());
+        Some(bytes[offset..end].to_vec())
     }
 
     fn store_write(&mut self, path: &str, data: &[u8], offset: usize) -> Result<(), ()> {
-        let buffer = self.storage.get(path).cloned();
-        match buffer {
-            None => {
-                self.storage.insert(path.to_string(), data.to_vec());
-                println!("there");
-                Ok(())
-            }
-            Some(mut buffer) => {
-                if offset == buffer.len() {
-                    println!("ici");
-                    let mut data = data.to_vec();
-                    buffer.append(&mut data);
-                    println!("{:?}", &buffer);
-                    self.storage.insert(path.to_string(), buffer.to_vec());
-                    Ok(())
-                } else {
-                    todo!()
-                }
-            }
+        // Like the kernel, rejects the chunks larger than what the host writes at once
+        if data.len() > MAX_FILE_CHUNK_SIZE {
+            return Err(());
         }
+        let buffer = self.storage.entry(path.to_string()).or_default();
+        if offset > buffer.len() {
+            return Err(());
+        }
+        // The written data can overwrite and extend the existing value
+        let end = usize::min(offset + data.len(), buffer.len());
+        buffer.splice(offset..end, data.iter().copied());
+        Ok(())
     }
 
     fn reveal_preimage(&mut self, _hash: &[u8; PREIMAGE_HASH_SIZE]) -> Result<Vec<u8>, ()> {
@@ -308,4 +388,16 @@ impl Runtime for MockRuntime {
     fn store_move(&mut self, _from: &str, _to: &str) -> Result<(), ()> {
         todo!()
     }
+
+    fn rollup_address(&mut self) -> Result<SmartRollupAddress, ()> {
+        Ok(self.rollup_address.clone())
+    }
+
+    fn write_output(&mut self, output: &[u8]) -> Result<(), ()> {
+        if output.len() > MAX_OUTPUT_SIZE {
+            return Err(());
+        }
+        self.outputs.push(output.to_vec());
+        Ok(())
+    }
 }
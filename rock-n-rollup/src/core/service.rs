@@ -17,7 +17,24 @@ where
 ////////// some types
 type TransitionFct<R, P, S> = dyn FnMut(&mut R, &Input<P>, &S) -> Result<(), ()>;
 
-type GuardFct<R, P> = dyn FnMut(&mut R, &Input<P>) -> bool;
+type GuardFct<R, P, S> = dyn FnMut(&mut R, &Input<P>, &S) -> bool;
+
+/// Value returned by a transition, a transition returning an error fails
+pub trait TransitionResult {
+    fn into_result(self) -> Result<(), ()>;
+}
+
+impl TransitionResult for () {
+    fn into_result(self) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+impl TransitionResult for Result<(), ()> {
+    fn into_result(self) -> Result<(), ()> {
+        self
+    }
+}
 
 pub trait IntoTransition<R, P, S, T>
 where
@@ -52,10 +69,11 @@ macro_rules! tuple_from_req {
             }
         }
 
-        impl<R, P, Fct, S, $($generic_param),*> IntoTransition<R, P, S, $struct_name<$($generic_param),*>> for Fct
+        impl<R, P, Fct, S, O, $($generic_param),*> IntoTransition<R, P, S, $struct_name<$($generic_param),*>> for Fct
             where
                 R: Runtime,
-                Fct: Fn(&mut R, $($generic_param),*) + 'static,
+                Fct: Fn(&mut R, $($generic_param),*) -> O + 'static,
+                O: TransitionResult,
                 $($generic_param: FromInput<P, S>),*,
                 P: Clone,
         {
@@ -65,25 +83,22 @@ macro_rules! tuple_from_req {
                         Ok(t) => t,
                         Err(_) => return Err(()),
                     };
-                    (self)(runtime, $(tuple.$generic_param),*);
-                    Ok(())
+                    (self)(runtime, $(tuple.$generic_param),*).into_result()
                 })
             }
         }
     };
 }
 
-impl<R, P, F, S> IntoTransition<R, P, S, ()> for F
+impl<R, P, F, S, O> IntoTransition<R, P, S, ()> for F
 where
     R: Runtime,
-    F: Fn(&mut R) + 'static,
+    F: Fn(&mut R) -> O + 'static,
+    O: TransitionResult,
 {
     fn into_transition(self) -> Box<dyn FnMut(&mut R, &Input<P>, &S) -> Result<(), ()>> {
         println!("Into transition");
-        Box::new(move |runtime: &mut R, _: &Input<P>, _: &S| {
-            (self)(runtime);
-            Ok(())
-        })
+        Box::new(move |runtime: &mut R, _: &Input<P>, _: &S| (self)(runtime).into_result())
     }
 }
 
@@ -97,12 +112,50 @@ tuple_from_req!(Tuple7; A, B, C, D, E, F, G);
 tuple_from_req!(Tuple8; A, B, C, D, E, F, G, H);
 tuple_from_req!(Tuple9; A, B, C, D, E, F, G, H, I);
 
+/// Result of the processing of a payload by a service
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The guards accepted the payload and every transition has been executed
+    Applied,
+    /// A guard did not accept the payload
+    Skipped,
+    /// A transition could not extract its parameters from the payload, or returned an error
+    Failed,
+}
+
 ///// FromInput implementation
 pub trait FromRawInput
 where
     Self: Sized,
 {
     fn from_raw_input<R: Runtime>(runtime: &mut R, input: &RawInput) -> Result<Self, ()>;
+
+    /// Extracts all the payloads carried by the input
+    ///
+    /// An input carries one payload by default, a batch can carry several of them.
+    /// Each payload is processed on its own, so one failure does not prevent the others from being processed
+    fn from_raw_input_batch<R: Runtime>(
+        runtime: &mut R,
+        input: &RawInput,
+    ) -> Vec<Result<Self, ()>> {
+        vec![Self::from_raw_input(runtime, input)]
+    }
+
+    /// Called before the guards and the transitions of the service process the payload
+    ///
+    /// Does nothing by default
+    fn processing<R: Runtime>(_runtime: &mut R, _input: &Input<Self>) {}
+
+    /// Called once the guards and the transitions of the service processed the payload
+    ///
+    /// Does nothing by default
+    fn processed<R: Runtime>(_runtime: &mut R, _input: &Input<Self>, _outcome: Outcome) {}
+
+    /// Called once every service of the application processed the input
+    ///
+    /// It can be called several times for the same input, when several services extract the same payload.
+    /// Does nothing by default
+    fn input_processed<R: Runtime>(_runtime: &mut R, _input: &RawInput) {}
 }
 
 impl<S> FromInput<Vec<u8>, S> for () {
@@ -147,7 +200,7 @@ pub struct Service<R, P, S>
 where
     P: FromRawInput,
 {
-    guards: Vec<Box<GuardFct<R, P>>>,
+    guards: Vec<Box<GuardFct<R, P, S>>>,
     transitions: Vec<Box<TransitionFct<R, P, S>>>,
     state: S,
 }
@@ -170,6 +223,9 @@ where
     R: Runtime,
 {
     fn run(&mut self, runtime: &mut R, input: RawInput);
+
+    /// Called once every service ran the input
+    fn finish(&mut self, _runtime: &mut R, _input: &RawInput) {}
 }
 
 impl<R, P, S> Runnable<R> for Service<R, P, S>
@@ -180,39 +236,63 @@ where
     fn run(&mut self, runtime: &mut R, input: RawInput) {
         println!("run");
 
-        let payload = match P::from_raw_input(runtime, &input) {
-            Ok(payload) => payload,
-            Err(_) => todo!("handle this error"),
-        };
-        println!("payload is present");
+        // Rejected payloads, or payloads that are not for this service, are ignored
+        let payloads = P::from_raw_input_batch(runtime, &input)
+            .into_iter()
+            .filter_map(Result::ok);
+
+        for payload in payloads {
+            let input = Input {
+                level: input.level,
+                id: input.id,
+                payload,
+            };
+            P::processing(runtime, &input);
+            let outcome = self.process(runtime, &input);
+            P::processed(runtime, &input, outcome);
+        }
+    }
 
-        // Get the raw input
-        let input = Input {
-            level: input.level,
-            id: input.id,
-            payload,
-        };
+    fn finish(&mut self, runtime: &mut R, input: &RawInput) {
+        P::input_processed(runtime, input);
+    }
+}
 
+impl<R, P, S> Service<R, P, S>
+where
+    R: Runtime,
+    P: FromRawInput,
+{
+    /// Runs the guards and the transitions on one payload
+    fn process(&mut self, runtime: &mut R, input: &Input<P>) -> Outcome {
         // Get the "state"
         let state = &self.state;
 
         // Run the guards
-        let accepted = self.guards.iter_mut().all(|guard| guard(runtime, &input));
+        let accepted = self
+            .guards
+            .iter_mut()
+            .all(|guard| guard(runtime, input, state));
 
         match accepted {
             false => {
                 println!("hmmmm");
                 // Do nothing on this message
+                Outcome::Skipped
             }
             true => {
                 println!("it's accepted");
                 // Now we can execute every transitions
                 println!("transitions: {}", self.transitions.len());
 
+                let mut outcome = Outcome::Applied;
                 for transition in self.transitions.iter_mut() {
                     println!("transition");
-                    let _ = transition(runtime, &input, state);
+                    if transition(runtime, input, state).is_err() {
+                        outcome = Outcome::Failed;
+                    }
                 }
+                outcome
             }
         }
     }
@@ -228,6 +308,18 @@ where
     /// It acts as a service, if the function returns true the message will be processed
     /// otherwise the message will be ignore
     pub fn add_guard(&mut self, guard: fn(&mut R, &Input<P>) -> bool) -> &mut Self {
+        let boxed = Box::new(move |runtime: &mut R, input: &Input<P>, _: &S| guard(runtime, input));
+        self.guards.push(boxed);
+        self
+    }
+
+    /// Add a guard reading the state of the service
+    ///
+    /// The payload is skipped when the function returns false, as with the other guards
+    pub fn add_state_guard(&mut self, guard: fn(&mut R, &Input<P>, &S) -> bool) -> &mut Self
+    where
+        S: 'static,
+    {
         let boxed = Box::new(guard);
         self.guards.push(boxed);
         self
@@ -237,6 +329,7 @@ where
     ///
     /// A transition can be any function of one or several parameters
     /// The transition function should take a runtime as first parameter and then other parameters should implement FromInput
+    /// It returns nothing, or a `Result<(), ()>` to report a failure
     pub fn register<F, Marker>(&mut self, transition: F) -> &mut Self
     where
         F: IntoTransition<R, P, S, Marker> + 'static,
@@ -271,7 +364,7 @@ where
 mod tests {
     use crate::core::{runtime::MockRuntime, Application, Runtime};
 
-    use super::{FromInput, IntoService, Service};
+    use super::{FromInput, Input, IntoService, Service};
 
     struct Test {
         inner: String,
@@ -416,4 +509,64 @@ mod tests {
 
         application.service(service).run();
     }
+
+    #[derive(Clone)]
+    struct GreetingService {
+        greetings: String,
+    }
+
+    #[rock_n_rollup::service]
+    impl GreetingService {
+        #[guard]
+        fn only_empty<R: Runtime>(_: &mut R, input: &Input<Vec<u8>>) -> bool {
+            input.payload.is_empty()
+        }
+
+        #[guard]
+        fn has_greetings<R: Runtime>(
+            _: &mut R,
+            _: &Input<Vec<u8>>,
+            state: &GreetingService,
+        ) -> bool {
+            !state.greetings.is_empty()
+        }
+
+        #[transition]
+        fn greet<R: Runtime>(rt: &mut R, state: GreetingService) {
+            rt.write_debug(&state.greetings);
+        }
+
+        #[transition]
+        fn greet_again<R: Runtime>(rt: &mut R, state: GreetingService) {
+            rt.write_debug(&format!("{} again", state.greetings));
+        }
+    }
+
+    #[test]
+    fn test_service_macro() {
+        let mut runtime = MockRuntime::default();
+        runtime.add_input(vec![0x01]).add_input(Vec::default());
+
+        let service = GreetingService {
+            greetings: "Hello world".to_string(),
+        };
+
+        Application::new(&mut runtime).service(service).run();
+
+        assert_eq!(runtime.stdout(), vec!["Hello world", "Hello world again"]);
+    }
+
+    #[test]
+    fn test_state_guard() {
+        let mut runtime = MockRuntime::default();
+        runtime.add_input(Vec::default());
+
+        let service = GreetingService {
+            greetings: String::default(),
+        };
+
+        Application::new(&mut runtime).service(service).run();
+
+        assert!(runtime.stdout().is_empty());
+    }
 }
@@ -0,0 +1,256 @@
+use std::fmt::Display;
+
+use blake2::digest::{Update, VariableOutput};
+use blake2::Blake2bVar;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::b58::{self, B58Check, B58Error};
+use crate::plugins::crypto::{Curve, PublicKey};
+
+/// Size of the hash of an address
+const HASH_SIZE: usize = 20;
+
+/// Address of an account, the hash of its public key
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ImplicitAddress {
+    /// Hash of an ed25519 public key
+    Tz1([u8; HASH_SIZE]),
+    /// Hash of a secp256k1 public key
+    Tz2([u8; HASH_SIZE]),
+    /// Hash of a P-256 public key
+    Tz3([u8; HASH_SIZE]),
+    /// Hash of a bls public key
+    Tz4([u8; HASH_SIZE]),
+}
+
+/// Address of a smart contract
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContractAddress([u8; HASH_SIZE]);
+
+/// Address of a smart rollup
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SmartRollupAddress([u8; HASH_SIZE]);
+
+/// Blake2b 160 bits of the given data, used to derive addresses
+fn blake2b_160(data: &[u8]) -> [u8; HASH_SIZE] {
+    let mut hasher = Blake2bVar::new(HASH_SIZE).unwrap();
+    hasher.update(data);
+    let mut buf = [0u8; HASH_SIZE];
+    hasher.finalize_variable(&mut buf).unwrap();
+    buf
+}
+
+fn to_hash(payload: Vec<u8>) -> Result<[u8; HASH_SIZE], B58Error> {
+    payload.try_into().map_err(|_| B58Error::InvalidPayload)
+}
+
+impl ImplicitAddress {
+    /// Address of a public key of the given curve, from the hash of the public key
+    pub fn from_hash(curve: Curve, hash: [u8; HASH_SIZE]) -> Self {
+        match curve {
+            Curve::Ed25519 => ImplicitAddress::Tz1(hash),
+            Curve::Secp256k1 => ImplicitAddress::Tz2(hash),
+            Curve::P256 => ImplicitAddress::Tz3(hash),
+            Curve::Bls => ImplicitAddress::Tz4(hash),
+        }
+    }
+
+    /// The curve of the hashed public key
+    pub fn curve(&self) -> Curve {
+        match self {
+            ImplicitAddress::Tz1(_) => Curve::Ed25519,
+            ImplicitAddress::Tz2(_) => Curve::Secp256k1,
+            ImplicitAddress::Tz3(_) => Curve::P256,
+            ImplicitAddress::Tz4(_) => Curve::Bls,
+        }
+    }
+
+    /// The hash of the public key
+    pub fn as_bytes(&self) -> &[u8; HASH_SIZE] {
+        match self {
+            ImplicitAddress::Tz1(hash)
+            | ImplicitAddress::Tz2(hash)
+            | ImplicitAddress::Tz3(hash)
+            | ImplicitAddress::Tz4(hash) => hash,
+        }
+    }
+}
+
+impl ContractAddress {
+    /// The hash of the contract
+    pub fn as_bytes(&self) -> &[u8; HASH_SIZE] {
+        &self.0
+    }
+}
+
+impl SmartRollupAddress {
+    /// The hash of the smart rollup
+    pub fn as_bytes(&self) -> &[u8; HASH_SIZE] {
+        &self.0
+    }
+}
+
+impl From<[u8; HASH_SIZE]> for SmartRollupAddress {
+    fn from(hash: [u8; HASH_SIZE]) -> Self {
+        SmartRollupAddress(hash)
+    }
+}
+
+impl From<&PublicKey> for ImplicitAddress {
+    fn from(public_key: &PublicKey) -> Self {
+        let hash = blake2b_160(&public_key.to_bytes());
+        ImplicitAddress::from_hash(public_key.curve(), hash)
+    }
+}
+
+impl B58Check for ImplicitAddress {
+    fn to_b58check(&self) -> String {
+        match self {
+            ImplicitAddress::Tz1(hash) => b58::encode(&b58::TZ1, hash),
+            ImplicitAddress::Tz2(hash) => b58::encode(&b58::TZ2, hash),
+            ImplicitAddress::Tz3(hash) => b58::encode(&b58::TZ3, hash),
+            ImplicitAddress::Tz4(hash) => b58::encode(&b58::TZ4, hash),
+        }
+    }
+
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error> {
+        let prefixes = [&b58::TZ1, &b58::TZ2, &b58::TZ3, &b58::TZ4];
+        let (prefix, hash) = b58::decode_any(&prefixes, encoded)?;
+        let hash = to_hash(hash)?;
+        match prefix.name {
+            "tz1" => Ok(ImplicitAddress::Tz1(hash)),
+            "tz2" => Ok(ImplicitAddress::Tz2(hash)),
+            "tz3" => Ok(ImplicitAddress::Tz3(hash)),
+            _ => Ok(ImplicitAddress::Tz4(hash)),
+        }
+    }
+}
+
+impl B58Check for ContractAddress {
+    fn to_b58check(&self) -> String {
+        b58::encode(&b58::KT1, &self.0)
+    }
+
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error> {
+        b58::decode(&b58::KT1, encoded)
+            .and_then(to_hash)
+            .map(ContractAddress)
+    }
+}
+
+impl B58Check for SmartRollupAddress {
+    fn to_b58check(&self) -> String {
+        b58::encode(&b58::SR1, &self.0)
+    }
+
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error> {
+        b58::decode(&b58::SR1, encoded)
+            .and_then(to_hash)
+            .map(SmartRollupAddress)
+    }
+}
+
+/// Implements Display, TryFrom<String> and serde as a base58check string
+macro_rules! impl_b58_string {
+    ($address:ty) => {
+        impl Display for $address {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.to_b58check())
+            }
+        }
+
+        impl TryFrom<String> for $address {
+            type Error = ();
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                <$address>::from_b58check(&value).map_err(|_| ())
+            }
+        }
+
+        impl Serialize for $address {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.to_b58check())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $address {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let encoded = String::deserialize(deserializer)?;
+                <$address>::from_b58check(&encoded).map_err(D::Error::custom)
+            }
+        }
+    };
+}
+
+impl_b58_string!(ImplicitAddress);
+impl_b58_string!(ContractAddress);
+impl_b58_string!(SmartRollupAddress);
+
+#[cfg(test)]
+mod tests {
+    use super::{ContractAddress, ImplicitAddress, SmartRollupAddress};
+    use crate::encoding::b58::{B58Check, B58Error};
+    use crate::plugins::crypto::PublicKey;
+
+    #[test]
+    fn test_derive_implicit_address() {
+        let keys = [
+            (
+                "edpkvGfYw3LyB1UcCahKQk4rF2tvbMUk8GFiTuMjL75uGXrpvKXhjn",
+                "tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb",
+            ),
+            (
+                "sppk7cxe98RSBipZShMfNucQiqbaHmeqLffBNGjUem8npuXwf67Hdge",
+                "tz2BrddNTwidCrSPdVwcsX7H6hbUCRjqLLmX",
+            ),
+            (
+                "p2pk65KHMTDrJHn1SPmGR1or7DniP9WUSVvHEa9EbjY4TyhUgopg269",
+                "tz3SePHi7ZYc22pM2XrytXtCWHjEHkVsBNr9",
+            ),
+        ];
+
+        for (public_key, address) in keys {
+            let public_key = PublicKey::from_b58check(public_key).unwrap();
+            let derived = ImplicitAddress::from(&public_key);
+            assert_eq!(derived.to_b58check(), address);
+            assert_eq!(ImplicitAddress::from_b58check(address).unwrap(), derived);
+        }
+    }
+
+    #[test]
+    fn test_address_prefixes() {
+        let contract = "KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ";
+        let implicit = "tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb";
+
+        let address = ContractAddress::from_b58check(contract).unwrap();
+        assert_eq!(address.to_b58check(), contract);
+
+        assert!(matches!(
+            ImplicitAddress::from_b58check(contract),
+            Err(B58Error::InvalidPrefix { .. })
+        ));
+        assert!(matches!(
+            ContractAddress::from_b58check(implicit),
+            Err(B58Error::InvalidPrefix { .. })
+        ));
+        assert!(matches!(
+            SmartRollupAddress::from_b58check(implicit),
+            Err(B58Error::InvalidPrefix { .. })
+        ));
+    }
+
+    #[test]
+    fn test_serde() {
+        let address = SmartRollupAddress([7; 20]);
+        let encoded = serde_json_wasm::to_string(&address).unwrap();
+
+        assert_eq!(encoded, format!("\"{}\"", address.to_b58check()));
+        let decoded = serde_json_wasm::from_str::<SmartRollupAddress>(&encoded).unwrap();
+        assert_eq!(decoded, address);
+
+        let address = ImplicitAddress::Tz4([7; 20]);
+        let encoded = bincode::serialize(&address).unwrap();
+        let decoded = bincode::deserialize::<ImplicitAddress>(&encoded).unwrap();
+        assert_eq!(decoded, address);
+    }
+}
@@ -0,0 +1,429 @@
+use std::fmt::Display;
+
+use base58::{FromBase58, ToBase58};
+use sha2::{Digest, Sha256};
+
+/// Size of the checksum appended to the data before the encoding
+const CHECKSUM_SIZE: usize = 4;
+
+/// Prefix of a base58check encoded value
+///
+/// The prefix bytes are chosen so that the encoded value starts with a human readable name
+#[derive(Debug, PartialEq, Eq)]
+pub struct Prefix {
+    /// Beginning of the encoded value, e.g. "tz1"
+    pub name: &'static str,
+    /// Bytes prepended to the payload before the encoding
+    pub bytes: &'static [u8],
+    /// Size of the payload
+    pub payload_size: usize,
+}
+
+/// tz1 address, the hash of an ed25519 public key
+pub const TZ1: Prefix = Prefix {
+    name: "tz1",
+    bytes: &[6, 161, 159],
+    payload_size: 20,
+};
+
+/// tz2 address, the hash of a secp256k1 public key
+pub const TZ2: Prefix = Prefix {
+    name: "tz2",
+    bytes: &[6, 161, 161],
+    payload_size: 20,
+};
+
+/// tz3 address, the hash of a p256 public key
+pub const TZ3: Prefix = Prefix {
+    name: "tz3",
+    bytes: &[6, 161, 164],
+    payload_size: 20,
+};
+
+/// tz4 address, the hash of a bls12-381 public key
+pub const TZ4: Prefix = Prefix {
+    name: "tz4",
+    bytes: &[6, 161, 166],
+    payload_size: 20,
+};
+
+/// Address of an originated contract
+pub const KT1: Prefix = Prefix {
+    name: "KT1",
+    bytes: &[2, 90, 121],
+    payload_size: 20,
+};
+
+/// Address of a smart rollup
+pub const SR1: Prefix = Prefix {
+    name: "sr1",
+    bytes: &[6, 124, 117],
+    payload_size: 20,
+};
+
+/// Ed25519 public key
+pub const EDPK: Prefix = Prefix {
+    name: "edpk",
+    bytes: &[13, 15, 37, 217],
+    payload_size: 32,
+};
+
+/// Secp256k1 public key
+pub const SPPK: Prefix = Prefix {
+    name: "sppk",
+    bytes: &[3, 254, 226, 86],
+    payload_size: 33,
+};
+
+/// P256 public key
+pub const P2PK: Prefix = Prefix {
+    name: "p2pk",
+    bytes: &[3, 178, 139, 127],
+    payload_size: 33,
+};
+
+/// Bls12-381 public key
+pub const BLPK: Prefix = Prefix {
+    name: "BLpk",
+    bytes: &[6, 149, 135, 204],
+    payload_size: 48,
+};
+
+/// Ed25519 seed
+pub const EDSK: Prefix = Prefix {
+    name: "edsk",
+    bytes: &[13, 15, 58, 7],
+    payload_size: 32,
+};
+
+/// Ed25519 secret key, the seed followed by the public key
+pub const EDSK_64: Prefix = Prefix {
+    name: "edsk",
+    bytes: &[43, 246, 78, 7],
+    payload_size: 64,
+};
+
+/// Secp256k1 secret key
+pub const SPSK: Prefix = Prefix {
+    name: "spsk",
+    bytes: &[17, 162, 224, 201],
+    payload_size: 32,
+};
+
+/// P256 secret key
+pub const P2SK: Prefix = Prefix {
+    name: "p2sk",
+    bytes: &[16, 81, 238, 189],
+    payload_size: 32,
+};
+
+/// Bls12-381 secret key
+pub const BLSK: Prefix = Prefix {
+    name: "BLsk",
+    bytes: &[3, 150, 192, 40],
+    payload_size: 32,
+};
+
+/// Ed25519 signature
+pub const EDSIG: Prefix = Prefix {
+    name: "edsig",
+    bytes: &[9, 245, 205, 134, 18],
+    payload_size: 64,
+};
+
+/// Secp256k1 signature
+pub const SPSIG: Prefix = Prefix {
+    name: "spsig1",
+    bytes: &[13, 115, 101, 19, 63],
+    payload_size: 64,
+};
+
+/// P256 signature
+pub const P2SIG: Prefix = Prefix {
+    name: "p2sig",
+    bytes: &[54, 240, 44, 52],
+    payload_size: 64,
+};
+
+/// Signature that does not carry its curve
+pub const SIG: Prefix = Prefix {
+    name: "sig",
+    bytes: &[4, 130, 43],
+    payload_size: 64,
+};
+
+/// Bls12-381 signature
+pub const BLSIG: Prefix = Prefix {
+    name: "BLsig",
+    bytes: &[40, 171, 64, 207],
+    payload_size: 96,
+};
+
+/// Hash of a smart rollup commitment
+pub const SRC1: Prefix = Prefix {
+    name: "src1",
+    bytes: &[17, 165, 134, 138],
+    payload_size: 32,
+};
+
+/// Hash of a smart rollup state
+pub const SRS1: Prefix = Prefix {
+    name: "srs1",
+    bytes: &[17, 165, 235, 240],
+    payload_size: 32,
+};
+
+/// Hash of a block
+pub const BLOCK_HASH: Prefix = Prefix {
+    name: "B",
+    bytes: &[1, 52],
+    payload_size: 32,
+};
+
+/// Hash of an operation
+pub const OPERATION_HASH: Prefix = Prefix {
+    name: "o",
+    bytes: &[5, 116],
+    payload_size: 32,
+};
+
+/// Hash of a protocol
+pub const PROTOCOL_HASH: Prefix = Prefix {
+    name: "P",
+    bytes: &[2, 170],
+    payload_size: 32,
+};
+
+/// Hash of a Michelson expression
+pub const SCRIPT_EXPR_HASH: Prefix = Prefix {
+    name: "expr",
+    bytes: &[13, 44, 64, 27],
+    payload_size: 32,
+};
+
+/// Identifier of a chain
+pub const CHAIN_ID: Prefix = Prefix {
+    name: "Net",
+    bytes: &[87, 82, 0],
+    payload_size: 4,
+};
+
+/// Errors that may occur when decoding a base58check value
+#[derive(Debug, PartialEq, Eq)]
+pub enum B58Error {
+    /// The value contains a character outside of the base58 alphabet
+    InvalidCharacter,
+    /// The value is too short to contain a prefix and a checksum
+    TooShort,
+    /// The checksum does not match the data
+    InvalidChecksum,
+    /// The value does not start with any of the expected prefixes
+    InvalidPrefix { expected: Vec<&'static str> },
+    /// The payload does not have the expected size
+    InvalidSize { expected: usize, actual: usize },
+    /// The payload is not valid for the decoded type
+    InvalidPayload,
+}
+
+impl Display for B58Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            B58Error::InvalidCharacter => write!(f, "invalid base58 character"),
+            B58Error::TooShort => write!(f, "the value is too short"),
+            B58Error::InvalidChecksum => write!(f, "invalid checksum"),
+            B58Error::InvalidPrefix { expected } => {
+                write!(f, "invalid prefix, expected {}", expected.join(", "))
+            }
+            B58Error::InvalidSize { expected, actual } => write!(
+                f,
+                "invalid payload size, expected {} bytes, got {}",
+                expected, actual
+            ),
+            B58Error::InvalidPayload => write!(f, "invalid payload"),
+        }
+    }
+}
+
+fn checksum(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(&Sha256::digest(data)).to_vec()[..CHECKSUM_SIZE].to_vec()
+}
+
+/// Encodes the payload with the given prefix
+pub fn encode(prefix: &Prefix, payload: &[u8]) -> String {
+    let mut data = prefix.bytes.to_vec();
+    data.extend(payload);
+    let checksum = checksum(&data);
+    data.extend(checksum);
+    data.to_base58()
+}
+
+/// Decodes a value and verifies its checksum
+///
+/// Returns the data without the checksum, the prefix is still present
+fn decode_checked(encoded: &str) -> Result<Vec<u8>, B58Error> {
+    let decoded = encoded
+        .from_base58()
+        .map_err(|_| B58Error::InvalidCharacter)?;
+
+    if decoded.len() < CHECKSUM_SIZE {
+        return Err(B58Error::TooShort);
+    }
+    let (data, expected) = decoded.split_at(decoded.len() - CHECKSUM_SIZE);
+    if checksum(data) != expected {
+        return Err(B58Error::InvalidChecksum);
+    }
+    Ok(data.to_vec())
+}
+
+/// Decodes a value encoded with the given prefix and returns its payload
+pub fn decode(prefix: &Prefix, encoded: &str) -> Result<Vec<u8>, B58Error> {
+    let (_, payload) = decode_any(&[prefix], encoded)?;
+    Ok(payload)
+}
+
+/// Decodes a value encoded with one of the given prefixes
+///
+/// Returns the matching prefix and the payload
+pub fn decode_any<'a>(
+    prefixes: &[&'a Prefix],
+    encoded: &str,
+) -> Result<(&'a Prefix, Vec<u8>), B58Error> {
+    let data = decode_checked(encoded)?;
+
+    // Several prefixes can have the same name (edsk), but not the same bytes
+    let prefix = prefixes
+        .iter()
+        .find(|prefix| data.starts_with(prefix.bytes))
+        .ok_or_else(|| B58Error::InvalidPrefix {
+            expected: prefixes.iter().map(|prefix| prefix.name).collect(),
+        })?;
+
+    let payload = data[prefix.bytes.len()..].to_vec();
+    if payload.len() != prefix.payload_size {
+        return Err(B58Error::InvalidSize {
+            expected: prefix.payload_size,
+            actual: payload.len(),
+        });
+    }
+
+    Ok((prefix, payload))
+}
+
+/// Types that can be encoded to/decoded from base58check
+pub trait B58Check
+where
+    Self: Sized,
+{
+    fn to_b58check(&self) -> String;
+
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every prefix with the size of the encoded values
+    const PREFIXES: [(&Prefix, usize); 27] = [
+        (&TZ1, 36),
+        (&TZ2, 36),
+        (&TZ3, 36),
+        (&TZ4, 36),
+        (&KT1, 36),
+        (&SR1, 36),
+        (&EDPK, 54),
+        (&SPPK, 55),
+        (&P2PK, 55),
+        (&BLPK, 76),
+        (&EDSK, 54),
+        (&EDSK_64, 98),
+        (&SPSK, 54),
+        (&P2SK, 54),
+        (&BLSK, 54),
+        (&EDSIG, 99),
+        (&SPSIG, 99),
+        (&P2SIG, 98),
+        (&SIG, 96),
+        (&BLSIG, 142),
+        (&SRC1, 54),
+        (&SRS1, 54),
+        (&BLOCK_HASH, 51),
+        (&OPERATION_HASH, 51),
+        (&PROTOCOL_HASH, 51),
+        (&SCRIPT_EXPR_HASH, 54),
+        (&CHAIN_ID, 15),
+    ];
+
+    #[test]
+    fn test_prefixes() {
+        for (prefix, size) in PREFIXES {
+            for byte in [0x00, 0xFF] {
+                let payload = vec![byte; prefix.payload_size];
+                let encoded = encode(prefix, &payload);
+
+                assert!(encoded.starts_with(prefix.name), "{}", encoded);
+                assert_eq!(encoded.len(), size, "{}", encoded);
+                assert_eq!(decode(prefix, &encoded), Ok(payload));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_address() {
+        let payload = decode(&KT1, "KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ").unwrap();
+
+        assert_eq!(
+            encode(&KT1, &payload),
+            "KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ"
+        );
+    }
+
+    #[test]
+    fn test_invalid_checksum() {
+        // The last character of a valid public key has been changed
+        let encoded = "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCfL";
+
+        assert_eq!(decode(&EDPK, encoded), Err(B58Error::InvalidChecksum));
+    }
+
+    #[test]
+    fn test_invalid_character() {
+        let encoded = "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCf0";
+
+        assert_eq!(decode(&EDPK, encoded), Err(B58Error::InvalidCharacter));
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        let encoded = "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCfK";
+
+        assert_eq!(
+            decode(&SPPK, encoded),
+            Err(B58Error::InvalidPrefix {
+                expected: vec!["sppk"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        let encoded = encode(&TZ1, &[0; 21]);
+
+        assert_eq!(
+            decode(&TZ1, &encoded),
+            Err(B58Error::InvalidSize {
+                expected: 20,
+                actual: 21
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_any() {
+        let encoded = encode(&EDSK_64, &[0; 64]);
+        let (prefix, payload) = decode_any(&[&EDSK, &EDSK_64], &encoded).unwrap();
+
+        assert_eq!(prefix, &EDSK_64);
+        assert_eq!(payload, vec![0; 64]);
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{de::DeserializeOwned, Serialize};
+use tezos_data_encoding::{enc::BinWriter, nom::NomReader};
+
+use crate::plugins::database::Backend;
+pub use crate::plugins::database::{Bincode, Json};
+
+/// Encode and decode a value of type T to/from bytes
+///
+/// It is used to decode the payload of messages, with the codec chosen by the user.
+pub trait Codec<T>
+where
+    T: Sized,
+{
+    fn encode(data: &T) -> Result<Vec<u8>, ()>;
+
+    fn decode(bytes: &[u8]) -> Result<T, ()>;
+}
+
+impl<T> Codec<T> for Json
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn encode(data: &T) -> Result<Vec<u8>, ()> {
+        <Json as Backend>::to_bytes(data)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, ()> {
+        <Json as Backend>::from_bytes(bytes)
+    }
+}
+
+impl<T> Codec<T> for Bincode
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn encode(data: &T) -> Result<Vec<u8>, ()> {
+        <Bincode as Backend>::to_bytes(data)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, ()> {
+        <Bincode as Backend>::from_bytes(bytes)
+    }
+}
+
+/// Tezos binary codec, the one used by the L1 for Michelson values
+pub struct TezosBinary {}
+
+impl<T> Codec<T> for TezosBinary
+where
+    T: NomReader + BinWriter,
+{
+    fn encode(data: &T) -> Result<Vec<u8>, ()> {
+        let mut output = Vec::default();
+        data.bin_write(&mut output).map_err(|_| ())?;
+        Ok(output)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, ()> {
+        match T::nom_read(bytes) {
+            Ok(([], data)) => Ok(data),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use nom::{branch::alt, bytes::complete::tag, combinator::value};
+use tezos_smart_rollup_encoding::michelson::{
+    ticket::Ticket, MichelsonBytes, MichelsonContract, MichelsonInt, MichelsonNat, MichelsonOption,
+    MichelsonOr, MichelsonPair, MichelsonString, MichelsonUnit,
+};
+
+pub use rock_n_rollup_macro::Michelson;
+pub use tezos_data_encoding::{
+    enc::{BinResult, BinWriter},
+    encoding::{Encoding, HasEncoding},
+    nom::{NomReader, NomResult},
+};
+pub use tezos_smart_rollup_encoding::michelson::Michelson;
+
+/// Binary encoding of the primitives: the tag of the node followed by the code of the primitive
+const UNIT: [u8; 2] = [0x03, 0x0B];
+const LEFT: [u8; 2] = [0x05, 0x05];
+const RIGHT: [u8; 2] = [0x05, 0x08];
+const PAIR: [u8; 2] = [0x07, 0x07];
+
+/// Type of a Michelson value, as written in a contract
+pub trait MichelsonType {
+    fn michelson_type() -> String;
+}
+
+impl MichelsonType for MichelsonUnit {
+    fn michelson_type() -> String {
+        String::from("unit")
+    }
+}
+
+impl MichelsonType for MichelsonInt {
+    fn michelson_type() -> String {
+        String::from("int")
+    }
+}
+
+impl MichelsonType for MichelsonNat {
+    fn michelson_type() -> String {
+        String::from("nat")
+    }
+}
+
+impl MichelsonType for MichelsonString {
+    fn michelson_type() -> String {
+        String::from("string")
+    }
+}
+
+impl MichelsonType for MichelsonBytes {
+    fn michelson_type() -> String {
+        String::from("bytes")
+    }
+}
+
+impl MichelsonType for MichelsonContract {
+    fn michelson_type() -> String {
+        String::from("address")
+    }
+}
+
+impl<T> MichelsonType for MichelsonOption<T>
+where
+    T: Michelson + MichelsonType,
+{
+    fn michelson_type() -> String {
+        format!("option {}", annotate(&T::michelson_type(), None))
+    }
+}
+
+impl<T> MichelsonType for Ticket<T>
+where
+    T: Michelson + MichelsonType,
+{
+    fn michelson_type() -> String {
+        format!("ticket {}", annotate(&T::michelson_type(), None))
+    }
+}
+
+impl<L, R> MichelsonType for MichelsonPair<L, R>
+where
+    L: Michelson + MichelsonType,
+    R: Michelson + MichelsonType,
+{
+    fn michelson_type() -> String {
+        pair(&[L::michelson_type(), R::michelson_type()])
+    }
+}
+
+impl<L, R> MichelsonType for MichelsonOr<L, R>
+where
+    L: Michelson + MichelsonType,
+    R: Michelson + MichelsonType,
+{
+    fn michelson_type() -> String {
+        or(&L::michelson_type(), &R::michelson_type())
+    }
+}
+
+/// Annotates a type, and wraps it in parentheses to give it as argument of another type
+///
+/// The annotation replaces the one of the type, if any:
+/// `annotate("pair nat bytes", Some("transfer"))` returns `(pair %transfer nat bytes)`
+pub fn annotate(ty: &str, annot: Option<&str>) -> String {
+    let ty = match ty.strip_prefix('(').and_then(|ty| ty.strip_suffix(')')) {
+        Some(ty) => ty,
+        None => ty,
+    };
+    let (primitive, arguments) = ty.split_once(' ').unwrap_or((ty, ""));
+    let (annot, arguments) = match (annot, arguments.strip_prefix('%')) {
+        (Some(annot), Some(annotated)) => {
+            let arguments = annotated
+                .split_once(' ')
+                .map_or("", |(_, arguments)| arguments);
+            (format!("%{}", annot), arguments)
+        }
+        (Some(annot), None) => (format!("%{}", annot), arguments),
+        (None, _) => (String::default(), arguments),
+    };
+
+    let ty = [primitive, &annot, arguments]
+        .iter()
+        .filter(|word| !word.is_empty())
+        .copied()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    match ty.contains(' ') {
+        true => format!("({})", ty),
+        false => ty,
+    }
+}
+
+/// Type of a right comb of pairs
+pub fn pair(types: &[String]) -> String {
+    let types = types
+        .iter()
+        .map(|ty| annotate(ty, None))
+        .collect::<Vec<String>>();
+    format!("pair {}", types.join(" "))
+}
+
+/// Type of an or
+pub fn or(left: &str, right: &str) -> String {
+    format!("or {} {}", annotate(left, None), annotate(right, None))
+}
+
+pub fn bin_write_unit(output: &mut Vec<u8>) {
+    output.extend_from_slice(&UNIT);
+}
+
+pub fn bin_write_pair(output: &mut Vec<u8>) {
+    output.extend_from_slice(&PAIR);
+}
+
+pub fn bin_write_left(output: &mut Vec<u8>) {
+    output.extend_from_slice(&LEFT);
+}
+
+pub fn bin_write_right(output: &mut Vec<u8>) {
+    output.extend_from_slice(&RIGHT);
+}
+
+pub fn nom_read_unit(input: &[u8]) -> NomResult<'_, ()> {
+    value((), tag(&UNIT[..]))(input)
+}
+
+/// Reads the primitive of a pair, its two arguments follow
+pub fn nom_read_pair(input: &[u8]) -> NomResult<'_, ()> {
+    value((), tag(&PAIR[..]))(input)
+}
+
+/// Reads the primitive of an or, returns true if it is a Left
+pub fn nom_read_or(input: &[u8]) -> NomResult<'_, bool> {
+    alt((value(true, tag(&LEFT[..])), value(false, tag(&RIGHT[..]))))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_data_encoding::types::Zarith;
+    use tezos_smart_rollup_encoding::{
+        contract::Contract,
+        michelson::{
+            ticket::Ticket, MichelsonBytes, MichelsonNat, MichelsonOption, MichelsonOr,
+            MichelsonPair, MichelsonUnit,
+        },
+    };
+
+    use super::{annotate, BinWriter, Michelson, MichelsonType};
+    use crate::core::{FromInput, Input, MockRuntime};
+    use crate::encoding::codec::{Codec, TezosBinary};
+    use crate::services::internal::{transfer_message, Internal, Transfer};
+
+    #[derive(Debug, PartialEq, Eq, Michelson)]
+    struct Deposit {
+        amount: MichelsonNat,
+        #[michelson(annot = "data")]
+        payload: MichelsonOption<MichelsonBytes>,
+        ticket: Ticket<MichelsonBytes>,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Michelson)]
+    enum Action {
+        Pause,
+        SetAdmin(MichelsonBytes),
+        Burn {
+            amount: MichelsonNat,
+            data: MichelsonBytes,
+        },
+    }
+
+    fn nat(n: u64) -> MichelsonNat {
+        MichelsonNat::new(Zarith(n.into())).unwrap()
+    }
+
+    fn encode<T: BinWriter>(value: &T) -> Vec<u8> {
+        let mut output = Vec::default();
+        value.bin_write(&mut output).unwrap();
+        output
+    }
+
+    fn assert_encoding<T, E>(value: T, expected: E)
+    where
+        T: Michelson,
+        E: Michelson,
+    {
+        let bytes = encode(&value);
+        assert_eq!(bytes, encode(&expected));
+        assert_eq!(<TezosBinary as Codec<T>>::decode(&bytes), Ok(value));
+    }
+
+    #[test]
+    fn test_annotate() {
+        assert_eq!(annotate("nat", None), "nat");
+        assert_eq!(annotate("nat", Some("amount")), "(nat %amount)");
+        assert_eq!(annotate("(nat %amount)", Some("value")), "(nat %value)");
+        assert_eq!(annotate("option nat", None), "(option nat)");
+        assert_eq!(
+            annotate("pair nat bytes", Some("transfer")),
+            "(pair %transfer nat bytes)"
+        );
+    }
+
+    #[test]
+    fn test_struct() {
+        assert_eq!(
+            Deposit::michelson_type(),
+            "pair (nat %amount) (option %data bytes) (ticket %ticket bytes)"
+        );
+
+        let ticket = || {
+            let ticketer = Contract::from_b58check("KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ").unwrap();
+            Ticket::new(ticketer, MichelsonBytes(vec![1]), 10).unwrap()
+        };
+        assert_encoding(
+            Deposit {
+                amount: nat(5),
+                payload: MichelsonOption(Some(MichelsonBytes(vec![2]))),
+                ticket: ticket(),
+            },
+            MichelsonPair(
+                nat(5),
+                MichelsonPair(MichelsonOption(Some(MichelsonBytes(vec![2]))), ticket()),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_inbox_transfer() {
+        let ticket = || {
+            let ticketer = Contract::from_b58check("KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ").unwrap();
+            Ticket::new(ticketer, MichelsonBytes(vec![1]), 10).unwrap()
+        };
+        let deposit = || Deposit {
+            amount: nat(5),
+            payload: MichelsonOption(None),
+            ticket: ticket(),
+        };
+
+        let input = Input {
+            level: 3,
+            id: 1,
+            payload: transfer_message("KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ", deposit()),
+        };
+        let mut runtime = MockRuntime::default();
+        let transfer =
+            Internal::<Transfer<Deposit>>::from_input(&mut runtime, &input, &()).unwrap();
+        assert_eq!(transfer.payload().payload(), &deposit());
+        assert_eq!(
+            transfer.payload().sender().to_string(),
+            "KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ"
+        );
+
+        // Another type cannot be read from the transfer
+        assert!(Internal::<Transfer<Action>>::from_input(&mut runtime, &input, &()).is_err());
+    }
+
+    #[test]
+    fn test_enum() {
+        assert_eq!(
+            Action::michelson_type(),
+            "or (unit %pause) (or (bytes %set_admin) (pair %burn (nat %amount) (bytes %data)))"
+        );
+
+        type Expected = MichelsonOr<
+            MichelsonUnit,
+            MichelsonOr<MichelsonBytes, MichelsonPair<MichelsonNat, MichelsonBytes>>,
+        >;
+        assert_encoding(Action::Pause, Expected::Left(MichelsonUnit));
+        assert_encoding(
+            Action::SetAdmin(MichelsonBytes(vec![1])),
+            Expected::Right(MichelsonOr::Left(MichelsonBytes(vec![1]))),
+        );
+        assert_encoding(
+            Action::Burn {
+                amount: nat(7),
+                data: MichelsonBytes(vec![2]),
+            },
+            Expected::Right(MichelsonOr::Right(MichelsonPair(
+                nat(7),
+                MichelsonBytes(vec![2]),
+            ))),
+        );
+    }
+}
@@ -0,0 +1,7 @@
+pub mod address;
+
+pub mod b58;
+
+pub mod codec;
+
+pub mod michelson;
@@ -1,5 +1,9 @@
+extern crate self as rock_n_rollup;
+
 pub mod core;
+pub mod encoding;
 pub mod plugins;
 pub mod services;
 
 pub use rock_n_rollup_macro::main;
+pub use rock_n_rollup_macro::service;
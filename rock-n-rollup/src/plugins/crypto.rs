@@ -1,82 +1,390 @@
-use base58::{FromBase58, ToBase58};
-use sha2::{Digest, Sha256};
+use bls12_381::{
+    hash_to_curve::{ExpandMsgXmd, HashToCurve},
+    multi_miller_loop, G1Affine, G2Affine, G2Prepared, G2Projective, Gt,
+};
+use k256::ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::core::Runtime;
+use crate::encoding::b58::{self, B58Check, B58Error};
 use crate::plugins::hasher::Hasher;
 
-/// TODO: extract this function in the core module
-/// And find a way to have a generic way to generate to_b58 and from_b58
-fn from_base58(prefix_size: usize, encoded: &str) -> Result<Vec<u8>, ()> {
-    let check_sum_size = 4;
-    let decoded = encoded.from_base58().map_err(|_| ())?;
-    let _payload = &decoded[..decoded.len() - check_sum_size]; // Remove the check sum
-    let bytes = decoded[prefix_size..].to_vec();
-    let bytes = bytes[..bytes.len() - check_sum_size].to_vec();
-    Ok(bytes)
-}
-
-/// TODO: extract this
-fn to_base58(prefix: Vec<u8>, data: &[u8]) -> String {
-    let mut result = prefix;
-    result.extend(data);
-    let checksum = Sha256::digest(&Sha256::digest(&result)).to_vec()[..4].to_vec();
-    result.extend(checksum);
-    result.to_base58()
-}
-
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PublicKey {
     /// tz1 address
     Ed25519(ed25519_compact::PublicKey),
+    /// tz2 address
+    Secp256k1(k256::ecdsa::VerifyingKey),
+    /// tz3 address
+    P256(p256::ecdsa::VerifyingKey),
+    /// tz4 address
+    Bls(G1Affine),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Signature {
     Ed25519(ed25519_compact::Signature),
+    Secp256k1(k256::ecdsa::Signature),
+    P256(p256::ecdsa::Signature),
+    Bls(G2Affine),
+}
+
+/// Secret key used to sign data, mainly useful in tests
+#[derive(Clone)]
+pub enum SecretKey {
+    Ed25519(ed25519_compact::SecretKey),
+    Secp256k1(k256::ecdsa::SigningKey),
+    P256(p256::ecdsa::SigningKey),
+}
+
+impl SecretKey {
+    /// Creates an ed25519 secret key from its 32 bytes seed
+    ///
+    /// Panics if the seed is only made of zeros
+    pub fn from_seed(seed: [u8; 32]) -> SecretKey {
+        let key_pair = ed25519_compact::KeyPair::from_seed(ed25519_compact::Seed::new(seed));
+        SecretKey::Ed25519(key_pair.sk)
+    }
+
+    /// The public key associated to the secret key
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            SecretKey::Ed25519(sk) => PublicKey::Ed25519(sk.public_key()),
+            SecretKey::Secp256k1(sk) => PublicKey::Secp256k1(*sk.verifying_key()),
+            SecretKey::P256(sk) => PublicKey::P256(*sk.verifying_key()),
+        }
+    }
+}
+
+/// Domain separation tag of the bls signatures of Tezos, which use the augmented scheme
+const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_AUG_";
+
+/// Hashes the message augmented with the public key to a point of G2
+fn bls_hash(public_key: &G1Affine, data: &[u8]) -> G2Affine {
+    let mut message = public_key.to_compressed().to_vec();
+    message.extend(data);
+    let point =
+        <G2Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(message, BLS_DST);
+    G2Affine::from(point)
+}
+
+/// Verifies a bls signature over several messages, each one signed by its public key
+///
+/// e(g1, signature) == e(pk_1, H(pk_1 || m_1)) * ... * e(pk_n, H(pk_n || m_n))
+fn bls_verify(signature: &G2Affine, messages: &[(&G1Affine, &[u8])]) -> bool {
+    let invalid_key = messages
+        .iter()
+        .any(|(public_key, _)| bool::from(public_key.is_identity()));
+    if messages.is_empty() || invalid_key {
+        return false;
+    }
+
+    let hashes = messages
+        .iter()
+        .map(|(public_key, data)| G2Prepared::from(bls_hash(public_key, data)))
+        .collect::<Vec<G2Prepared>>();
+    let generator = -G1Affine::generator();
+    let signature = G2Prepared::from(*signature);
+
+    let mut terms = vec![(&generator, &signature)];
+    terms.extend(
+        messages
+            .iter()
+            .zip(hashes.iter())
+            .map(|((public_key, _), hash)| (*public_key, hash)),
+    );
+
+    multi_miller_loop(&terms).final_exponentiation() == Gt::identity()
+}
+
+impl Signature {
+    /// Aggregates several bls signatures into one
+    pub fn aggregate(signatures: &[Signature]) -> Result<Signature, ()> {
+        if signatures.is_empty() {
+            return Err(());
+        }
+        signatures
+            .iter()
+            .try_fold(G2Projective::identity(), |acc, signature| match signature {
+                Signature::Bls(signature) => Ok(acc + signature),
+                _ => Err(()),
+            })
+            .map(|aggregated| Signature::Bls(G2Affine::from(aggregated)))
+    }
+}
+
+/// Curve of a public key or of a signature
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    Ed25519,
+    Secp256k1,
+    P256,
+    Bls,
+}
+
+impl Curve {
+    /// Tag of the curve in the binary encodings
+    pub fn tag(&self) -> u8 {
+        match self {
+            Curve::Ed25519 => 0,
+            Curve::Secp256k1 => 1,
+            Curve::P256 => 2,
+            Curve::Bls => 3,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Result<Curve, ()> {
+        match tag {
+            0 => Ok(Curve::Ed25519),
+            1 => Ok(Curve::Secp256k1),
+            2 => Ok(Curve::P256),
+            3 => Ok(Curve::Bls),
+            _ => Err(()),
+        }
+    }
+}
+
+impl PublicKey {
+    pub fn curve(&self) -> Curve {
+        match self {
+            PublicKey::Ed25519(_) => Curve::Ed25519,
+            PublicKey::Secp256k1(_) => Curve::Secp256k1,
+            PublicKey::P256(_) => Curve::P256,
+            PublicKey::Bls(_) => Curve::Bls,
+        }
+    }
+
+    /// Raw bytes of the public key, ecdsa keys are compressed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            PublicKey::Ed25519(ed25519) => ed25519.to_vec(),
+            PublicKey::Secp256k1(secp256k1) => secp256k1.to_encoded_point(true).as_bytes().to_vec(),
+            PublicKey::P256(p256) => p256.to_encoded_point(true).as_bytes().to_vec(),
+            PublicKey::Bls(bls) => bls.to_compressed().to_vec(),
+        }
+    }
+
+    pub fn from_bytes(curve: Curve, bytes: &[u8]) -> Result<PublicKey, ()> {
+        match curve {
+            Curve::Ed25519 => ed25519_compact::PublicKey::from_slice(bytes)
+                .map(PublicKey::Ed25519)
+                .map_err(|_| ()),
+            Curve::Secp256k1 => k256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(PublicKey::Secp256k1)
+                .map_err(|_| ()),
+            Curve::P256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(PublicKey::P256)
+                .map_err(|_| ()),
+            Curve::Bls => {
+                let bytes = bytes.try_into().map_err(|_| ())?;
+                Option::from(G1Affine::from_compressed(bytes))
+                    .map(PublicKey::Bls)
+                    .ok_or(())
+            }
+        }
+    }
+}
+
+impl Signature {
+    pub fn curve(&self) -> Curve {
+        match self {
+            Signature::Ed25519(_) => Curve::Ed25519,
+            Signature::Secp256k1(_) => Curve::Secp256k1,
+            Signature::P256(_) => Curve::P256,
+            Signature::Bls(_) => Curve::Bls,
+        }
+    }
+
+    /// Raw bytes of the signature
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Signature::Ed25519(ed25519) => ed25519.to_vec(),
+            Signature::Secp256k1(secp256k1) => secp256k1.to_bytes().to_vec(),
+            Signature::P256(p256) => p256.to_bytes().to_vec(),
+            Signature::Bls(bls) => bls.to_compressed().to_vec(),
+        }
+    }
+
+    pub fn from_bytes(curve: Curve, bytes: &[u8]) -> Result<Signature, ()> {
+        match curve {
+            Curve::Ed25519 => ed25519_compact::Signature::from_slice(bytes)
+                .map(Signature::Ed25519)
+                .map_err(|_| ()),
+            Curve::Secp256k1 => k256::ecdsa::Signature::from_slice(bytes)
+                .map(Signature::Secp256k1)
+                .map_err(|_| ()),
+            Curve::P256 => p256::ecdsa::Signature::from_slice(bytes)
+                .map(Signature::P256)
+                .map_err(|_| ()),
+            Curve::Bls => {
+                let bytes = bytes.try_into().map_err(|_| ())?;
+                Option::from(G2Affine::from_compressed(bytes))
+                    .map(Signature::Bls)
+                    .ok_or(())
+            }
+        }
+    }
+}
+
+impl B58Check for PublicKey {
+    fn to_b58check(&self) -> String {
+        let prefix = match self.curve() {
+            Curve::Ed25519 => &b58::EDPK,
+            Curve::Secp256k1 => &b58::SPPK,
+            Curve::P256 => &b58::P2PK,
+            Curve::Bls => &b58::BLPK,
+        };
+        b58::encode(prefix, &self.to_bytes())
+    }
+
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error> {
+        let prefixes = [&b58::EDPK, &b58::SPPK, &b58::P2PK, &b58::BLPK];
+        let (prefix, public_key) = b58::decode_any(&prefixes, encoded)?;
+        let curve = match prefix.name {
+            "edpk" => Curve::Ed25519,
+            "sppk" => Curve::Secp256k1,
+            "p2pk" => Curve::P256,
+            _ => Curve::Bls,
+        };
+        PublicKey::from_bytes(curve, &public_key).map_err(|_| B58Error::InvalidPayload)
+    }
+}
+
+impl B58Check for Signature {
+    fn to_b58check(&self) -> String {
+        let prefix = match self.curve() {
+            Curve::Ed25519 => &b58::EDSIG,
+            Curve::Secp256k1 => &b58::SPSIG,
+            Curve::P256 => &b58::P2SIG,
+            Curve::Bls => &b58::BLSIG,
+        };
+        b58::encode(prefix, &self.to_bytes())
+    }
+
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error> {
+        let prefixes = [&b58::EDSIG, &b58::SPSIG, &b58::P2SIG, &b58::BLSIG];
+        let (prefix, signature) = b58::decode_any(&prefixes, encoded)?;
+        let curve = match prefix.name {
+            "edsig" => Curve::Ed25519,
+            "spsig1" => Curve::Secp256k1,
+            "p2sig" => Curve::P256,
+            _ => Curve::Bls,
+        };
+        Signature::from_bytes(curve, &signature).map_err(|_| B58Error::InvalidPayload)
+    }
+}
+
+impl B58Check for SecretKey {
+    fn to_b58check(&self) -> String {
+        match self {
+            SecretKey::Ed25519(sk) => b58::encode(&b58::EDSK, sk.seed().as_ref()),
+            SecretKey::Secp256k1(sk) => b58::encode(&b58::SPSK, &sk.to_bytes()),
+            SecretKey::P256(sk) => b58::encode(&b58::P2SK, &sk.to_bytes()),
+        }
+    }
+
+    /// Ed25519 secret keys can be encoded as a 32 bytes seed or as a 64 bytes key
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error> {
+        let prefixes = [&b58::EDSK, &b58::EDSK_64, &b58::SPSK, &b58::P2SK];
+        let (prefix, secret_key) = b58::decode_any(&prefixes, encoded)?;
+        match (prefix.name, prefix.payload_size) {
+            ("edsk", 32) if secret_key.iter().all(|byte| *byte == 0) => {
+                Err(B58Error::InvalidPayload)
+            }
+            ("edsk", 32) => ed25519_compact::Seed::from_slice(&secret_key)
+                .map(|seed| SecretKey::Ed25519(ed25519_compact::KeyPair::from_seed(seed).sk))
+                .map_err(|_| B58Error::InvalidPayload),
+            ("edsk", _) => ed25519_compact::SecretKey::from_slice(&secret_key)
+                .map(SecretKey::Ed25519)
+                .map_err(|_| B58Error::InvalidPayload),
+            ("spsk", _) => k256::ecdsa::SigningKey::from_slice(&secret_key)
+                .map(SecretKey::Secp256k1)
+                .map_err(|_| B58Error::InvalidPayload),
+            _ => p256::ecdsa::SigningKey::from_slice(&secret_key)
+                .map(SecretKey::P256)
+                .map_err(|_| B58Error::InvalidPayload),
+        }
+    }
+}
+
+impl TryFrom<String> for SecretKey {
+    type Error = ();
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        SecretKey::from_b58check(&value).map_err(|_| ())
+    }
 }
 
 impl TryFrom<String> for PublicKey {
     type Error = ();
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let public_key = from_base58(4, &value)?;
-        let ed25519 = ed25519_compact::PublicKey::from_slice(&public_key).map_err(|_| ())?;
-        Ok(PublicKey::Ed25519(ed25519))
+        PublicKey::from_b58check(&value).map_err(|_| ())
+    }
+}
+
+/// Public keys and signatures are serialized as base58check strings
+impl Serialize for PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_b58check())
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        PublicKey::from_b58check(&encoded).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_b58check())
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        Signature::from_b58check(&encoded).map_err(D::Error::custom)
     }
 }
 
 impl ToString for PublicKey {
     fn to_string(&self) -> String {
-        match self {
-            PublicKey::Ed25519(ed25519) => {
-                let bytes = ed25519.as_ref();
-
-                to_base58(vec![13, 15, 37, 217], bytes)
-            }
-        }
+        self.to_b58check()
     }
 }
 
 impl TryFrom<String> for Signature {
     type Error = ();
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let signature = from_base58(5, &value)?;
-        let sig = ed25519_compact::Signature::from_slice(&signature).map_err(|_| ())?;
-        Ok(Signature::Ed25519(sig))
+        Signature::from_b58check(&value).map_err(|_| ())
     }
 }
 
 pub trait Verifier {
     /// Verify the signature for a given public key over the hash of the given data
     ///
-    /// The data passed as parameter will be hashed
+    /// The data passed as parameter will be hashed with blake2b, as octez-client does
     /// Then the public will be used to verify that the signature is indeed the signature of the generated hash
+    ///
+    /// Bls signatures are verified over the data itself, the hash being part of the bls scheme
     fn verify_signature(
         &mut self,
         signature: &Signature,
         public_key: &PublicKey,
         data: &[u8],
     ) -> bool;
+
+    /// Verify an aggregated bls signature over several messages
+    ///
+    /// Each message is associated to the public key that signed it
+    fn verify_aggregate_signature(
+        &mut self,
+        signature: &Signature,
+        messages: &[(&PublicKey, &[u8])],
+    ) -> bool;
 }
 
 impl<R> Verifier for R
@@ -89,6 +397,10 @@ where
         public_key: &PublicKey,
         data: &[u8],
     ) -> bool {
+        if let (Signature::Bls(sig), PublicKey::Bls(pkey)) = (signature, public_key) {
+            return bls_verify(sig, &[(pkey, data)]);
+        }
+
         let data = self.hash(data);
 
         match (signature, public_key) {
@@ -96,14 +408,73 @@ where
                 let res = pkey.verify(data, sig);
                 res.is_ok()
             }
+            // The hash is already computed, it is used as the prehash of the ecdsa signature
+            (Signature::Secp256k1(sig), PublicKey::Secp256k1(pkey)) => {
+                pkey.verify_prehash(data.as_ref(), sig).is_ok()
+            }
+            (Signature::P256(sig), PublicKey::P256(pkey)) => {
+                pkey.verify_prehash(data.as_ref(), sig).is_ok()
+            }
+            _ => false,
+        }
+    }
+
+    fn verify_aggregate_signature(
+        &mut self,
+        signature: &Signature,
+        messages: &[(&PublicKey, &[u8])],
+    ) -> bool {
+        let signature = match signature {
+            Signature::Bls(signature) => signature,
+            _ => return false,
+        };
+        let messages = messages
+            .iter()
+            .map(|(public_key, data)| match public_key {
+                PublicKey::Bls(public_key) => Some((public_key, *data)),
+                _ => None,
+            })
+            .collect::<Option<Vec<(&G1Affine, &[u8])>>>();
+
+        match messages {
+            Some(messages) => bls_verify(signature, &messages),
+            None => false,
+        }
+    }
+}
+
+pub trait Signer {
+    /// Sign the hash of the given data with the secret key
+    ///
+    /// The data is hashed with blake2b, as octez-client does, so the signature can be checked with the Verifier
+    fn sign(&mut self, secret_key: &SecretKey, data: &[u8]) -> Signature;
+}
+
+impl<R> Signer for R
+where
+    R: Runtime + Hasher,
+{
+    fn sign(&mut self, secret_key: &SecretKey, data: &[u8]) -> Signature {
+        let data = self.hash(data);
+
+        match secret_key {
+            SecretKey::Ed25519(sk) => Signature::Ed25519(sk.sign(data, None)),
+            // Ecdsa signatures are deterministic (RFC 6979)
+            SecretKey::Secp256k1(sk) => {
+                Signature::Secp256k1(sk.sign_prehash(data.as_ref()).unwrap())
+            }
+            SecretKey::P256(sk) => Signature::P256(sk.sign_prehash(data.as_ref()).unwrap()),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{PublicKey, Signature, Verifier};
+    use bls12_381::{G1Affine, Scalar};
+
+    use super::{bls_hash, PublicKey, SecretKey, Signature, Signer, Verifier};
     use crate::core::MockRuntime;
+    use crate::encoding::b58::{B58Check, B58Error};
 
     #[test]
     fn test_ed25519_pkey_deserialization() {
@@ -129,6 +500,23 @@ mod tests {
         assert!(signature.is_ok())
     }
 
+    #[test]
+    fn test_ed25519_signature_serialization() {
+        let encoded = "edsigtuU5nUqBniorqTFXFixkG6ZkfvEPrfc9aT9DnMAeims2AX2yjpgYaedXBoKzAGHE3ZXSi1hZz6piZ3itTE7f2F4FoaxXtM";
+        let signature = Signature::from_b58check(encoded).unwrap();
+
+        assert_eq!(signature.to_b58check(), encoded);
+    }
+
+    #[test]
+    fn test_ed25519_pkey_invalid_checksum() {
+        // The last character has been changed
+        let string = "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCfL";
+        let pkey = PublicKey::from_b58check(string);
+
+        assert_eq!(pkey.err(), Some(B58Error::InvalidChecksum));
+    }
+
     #[test]
     fn test_verify_ed25519_signature() {
         let mut rt = MockRuntime::default();
@@ -147,4 +535,184 @@ mod tests {
 
         assert!(is_ok);
     }
+
+    // The following keys and signatures are deterministic RFC 6979 signatures
+    // of the blake2b-256 hash of "hello world"
+    const SECP256K1_PUBLIC_KEY: &str = "sppk7cxe98RSBipZShMfNucQiqbaHmeqLffBNGjUem8npuXwf67Hdge";
+    const SECP256K1_SIGNATURE: &str = "spsig1MSpgD1GJx7fbKkxwgswfvkvTx6eQc3BumVH9dYodW4Wqyid5T4zM6nuLJz2mn66jtSmq3xt4FCxAFCjWJ4D775Y4BqNn8";
+    const P256_PUBLIC_KEY: &str = "p2pk65KHMTDrJHn1SPmGR1or7DniP9WUSVvHEa9EbjY4TyhUgopg269";
+    const P256_SIGNATURE: &str = "p2sigUKrHUZvkfpLnNHFcvBBctFRo5zB9o5zj26USV4Di4JATSV116nKHARQ7pewdt2FqfsjbRzvUQ1mxbaqWdWn2FbqzMEcmo";
+
+    #[test]
+    fn test_secp256k1_serialization() {
+        let pkey = PublicKey::from_b58check(SECP256K1_PUBLIC_KEY).unwrap();
+        let signature = Signature::from_b58check(SECP256K1_SIGNATURE).unwrap();
+
+        assert!(matches!(pkey, PublicKey::Secp256k1(_)));
+        assert_eq!(pkey.to_b58check(), SECP256K1_PUBLIC_KEY);
+        assert_eq!(signature.to_b58check(), SECP256K1_SIGNATURE);
+    }
+
+    #[test]
+    fn test_p256_serialization() {
+        let pkey = PublicKey::from_b58check(P256_PUBLIC_KEY).unwrap();
+        let signature = Signature::from_b58check(P256_SIGNATURE).unwrap();
+
+        assert!(matches!(pkey, PublicKey::P256(_)));
+        assert_eq!(pkey.to_b58check(), P256_PUBLIC_KEY);
+        assert_eq!(signature.to_b58check(), P256_SIGNATURE);
+    }
+
+    #[test]
+    fn test_verify_secp256k1_signature() {
+        let mut rt = MockRuntime::default();
+        let public_key = PublicKey::from_b58check(SECP256K1_PUBLIC_KEY).unwrap();
+        let signature = Signature::from_b58check(SECP256K1_SIGNATURE).unwrap();
+
+        assert!(rt.verify_signature(&signature, &public_key, "hello world".as_bytes()));
+        assert!(!rt.verify_signature(&signature, &public_key, "hello".as_bytes()));
+    }
+
+    #[test]
+    fn test_verify_p256_signature() {
+        let mut rt = MockRuntime::default();
+        let public_key = PublicKey::from_b58check(P256_PUBLIC_KEY).unwrap();
+        let signature = Signature::from_b58check(P256_SIGNATURE).unwrap();
+
+        assert!(rt.verify_signature(&signature, &public_key, "hello world".as_bytes()));
+        assert!(!rt.verify_signature(&signature, &public_key, "hello".as_bytes()));
+    }
+
+    #[test]
+    fn test_verify_mismatching_curves() {
+        let mut rt = MockRuntime::default();
+        let public_key = PublicKey::from_b58check(P256_PUBLIC_KEY).unwrap();
+        let signature = Signature::from_b58check(SECP256K1_SIGNATURE).unwrap();
+
+        assert!(!rt.verify_signature(&signature, &public_key, "hello world".as_bytes()));
+    }
+
+    /// Generates a bls key pair and signs the message, as a bls signer would do
+    fn bls_sign(secret_key: u64, data: &[u8]) -> (PublicKey, Signature) {
+        let secret_key = Scalar::from(secret_key);
+        let public_key = G1Affine::from(G1Affine::generator() * secret_key);
+        let signature = bls_hash(&public_key, data) * secret_key;
+        (PublicKey::Bls(public_key), Signature::Bls(signature.into()))
+    }
+
+    #[test]
+    fn test_bls_serialization() {
+        let (public_key, signature) = bls_sign(42, "hello world".as_bytes());
+
+        let encoded = public_key.to_b58check();
+        assert!(encoded.starts_with("BLpk"));
+        let decoded = PublicKey::from_b58check(&encoded).unwrap();
+        assert_eq!(decoded.to_b58check(), encoded);
+
+        let encoded = signature.to_b58check();
+        assert!(encoded.starts_with("BLsig"));
+        let decoded = Signature::from_b58check(&encoded).unwrap();
+        assert_eq!(decoded.to_b58check(), encoded);
+    }
+
+    #[test]
+    fn test_verify_bls_signature() {
+        let mut rt = MockRuntime::default();
+        let (public_key, signature) = bls_sign(42, "hello world".as_bytes());
+        let (other_key, _) = bls_sign(43, "hello world".as_bytes());
+
+        assert!(rt.verify_signature(&signature, &public_key, "hello world".as_bytes()));
+        assert!(!rt.verify_signature(&signature, &public_key, "hello".as_bytes()));
+        assert!(!rt.verify_signature(&signature, &other_key, "hello world".as_bytes()));
+    }
+
+    #[test]
+    fn test_verify_bls_aggregate_signature() {
+        let mut rt = MockRuntime::default();
+        let (alice, alice_signature) = bls_sign(1, "first operation".as_bytes());
+        let (bob, bob_signature) = bls_sign(2, "second operation".as_bytes());
+        let (carol, carol_signature) = bls_sign(3, "first operation".as_bytes());
+
+        let signature =
+            Signature::aggregate(&[alice_signature, bob_signature, carol_signature]).unwrap();
+
+        let messages = [
+            (&alice, "first operation".as_bytes()),
+            (&bob, "second operation".as_bytes()),
+            (&carol, "first operation".as_bytes()),
+        ];
+        assert!(rt.verify_aggregate_signature(&signature, &messages));
+
+        // One of the messages has been tampered
+        let messages = [
+            (&alice, "first operation".as_bytes()),
+            (&bob, "third operation".as_bytes()),
+            (&carol, "first operation".as_bytes()),
+        ];
+        assert!(!rt.verify_aggregate_signature(&signature, &messages));
+
+        // One of the signers is missing
+        let messages = [
+            (&alice, "first operation".as_bytes()),
+            (&bob, "second operation".as_bytes()),
+        ];
+        assert!(!rt.verify_aggregate_signature(&signature, &messages));
+        assert!(!rt.verify_aggregate_signature(&signature, &[]));
+    }
+
+    #[test]
+    fn test_aggregate_non_bls_signatures() {
+        let signature = Signature::from_b58check(P256_SIGNATURE).unwrap();
+
+        assert!(Signature::aggregate(&[signature]).is_err());
+        assert!(Signature::aggregate(&[]).is_err());
+    }
+
+    #[test]
+    fn test_secret_key_serialization() {
+        let keys = [
+            (
+                "edsk3QoqBuvdamxouPhin7swCvkQNgq4jP5KZPbwWNnwdZpSpJiEbq",
+                "edpkvGfYw3LyB1UcCahKQk4rF2tvbMUk8GFiTuMjL75uGXrpvKXhjn",
+            ),
+            (
+                "spsk2hisJy34oaT3XNBWTsKqqKtVLknQW1rQ8Yw6ogopxdiRqnAUrc",
+                SECP256K1_PUBLIC_KEY,
+            ),
+            (
+                "p2sk3S6JUVDafcfkL8JdhgBodFBndiYbwXof1EZh46jznsCEbk3wLw",
+                P256_PUBLIC_KEY,
+            ),
+        ];
+
+        for (secret_key, public_key) in keys {
+            let sk = SecretKey::from_b58check(secret_key).unwrap();
+            assert_eq!(sk.to_b58check(), secret_key);
+            assert_eq!(sk.public_key().to_b58check(), public_key);
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        let mut rt = MockRuntime::default();
+        let data = "hello world".as_bytes();
+
+        let sk = SecretKey::from_seed([7; 32]);
+        let signature = rt.sign(&sk, data);
+        assert!(rt.verify_signature(&signature, &sk.public_key(), data));
+        assert!(!rt.verify_signature(&signature, &sk.public_key(), "hello".as_bytes()));
+
+        // Ecdsa signatures are deterministic
+        let sk = SecretKey::try_from(
+            "spsk2hisJy34oaT3XNBWTsKqqKtVLknQW1rQ8Yw6ogopxdiRqnAUrc".to_string(),
+        )
+        .unwrap();
+        assert_eq!(rt.sign(&sk, data).to_b58check(), SECP256K1_SIGNATURE);
+
+        let sk = SecretKey::try_from(
+            "p2sk3S6JUVDafcfkL8JdhgBodFBndiYbwXof1EZh46jznsCEbk3wLw".to_string(),
+        )
+        .unwrap();
+        assert_eq!(rt.sign(&sk, data).to_b58check(), P256_SIGNATURE);
+    }
 }
@@ -80,6 +80,10 @@ where
     where
         D: DeserializeOwned,
     {
+        if !self.store_is_present(path) {
+            return Ok(None);
+        }
+
         // The n first bytes represent the size
         let usize_size = size_of::<usize>();
         let size = self
@@ -142,6 +146,29 @@ mod tests {
         assert_eq!(greetings, data)
     }
 
+    #[test]
+    fn test_missing_and_overwritten_value() {
+        let mut runtime = MockRuntime::default();
+
+        let missing = <MockRuntime as Database<Bincode>>::get::<u64>(&mut runtime, "/counter");
+        assert_eq!(missing, Ok(None));
+
+        let _ = <MockRuntime as Database<Bincode>>::save(&mut runtime, "/counter", &1u64).unwrap();
+        let _ = <MockRuntime as Database<Bincode>>::save(&mut runtime, "/counter", &2u64).unwrap();
+        let counter = <MockRuntime as Database<Bincode>>::get::<u64>(&mut runtime, "/counter");
+        assert_eq!(counter, Ok(Some(2)));
+    }
+
+    #[test]
+    fn test_large_value() {
+        let mut runtime = MockRuntime::default();
+
+        // Like the kernel, the MockRuntime cannot write more than 2048 bytes at once
+        let data = vec![0u8; 2048];
+        let saved = <MockRuntime as Database<Bincode>>::save(&mut runtime, "/large", &data);
+        assert!(saved.is_err());
+    }
+
     #[test]
     fn test_json() {
         test_backend::<Json>()
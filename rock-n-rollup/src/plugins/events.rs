@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::Runtime;
+use crate::plugins::database::{Backend, Bincode, Database, Json};
+
+/// Path of the events emitted while processing the current input
+///
+/// Each event is saved under its own path `/events/pending/<index>`, so emitting an event does not rewrite the others
+const PENDING_EVENTS_PATH: &str = "/events/pending";
+
+/// Path of the number of pending events
+const PENDING_COUNT_PATH: &str = "/events/pending/count";
+
+fn event_path(index: usize) -> String {
+    format!("{}/{}", PENDING_EVENTS_PATH, index)
+}
+
+/// Event emitted by a transition
+///
+/// The data of the event is encoded in json
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub name: String,
+    pub data: String,
+}
+
+pub trait Events {
+    /// Emits an event, the data is encoded in json
+    ///
+    /// The events are collected in the receipt of the operation being processed
+    fn emit<D: Serialize>(&mut self, name: &str, data: &D) -> Result<(), ()>;
+
+    /// Returns the pending events and removes them from the durable storage
+    fn take_events(&mut self) -> Result<Vec<Event>, ()>;
+
+    /// Returns the number of pending events
+    fn pending_events(&mut self) -> Result<usize, ()>;
+
+    /// Returns the pending events emitted after the first `count` ones, and removes them from the durable storage
+    ///
+    /// The first `count` events stay pending
+    fn take_events_after(&mut self, count: usize) -> Result<Vec<Event>, ()>;
+}
+
+impl<R> Events for R
+where
+    R: Runtime,
+{
+    fn emit<D: Serialize>(&mut self, name: &str, data: &D) -> Result<(), ()> {
+        let data = Json::to_bytes(data)?;
+        let event = Event {
+            name: name.to_string(),
+            data: String::from_utf8(data).map_err(|_| ())?,
+        };
+
+        let count = self.pending_events()?;
+        <R as Database<Bincode>>::save(self, &event_path(count), &event)?;
+        <R as Database<Bincode>>::save(self, PENDING_COUNT_PATH, &(count + 1)).map(|_| ())
+    }
+
+    fn take_events(&mut self) -> Result<Vec<Event>, ()> {
+        self.take_events_after(0)
+    }
+
+    fn pending_events(&mut self) -> Result<usize, ()> {
+        let count = <R as Database<Bincode>>::get(self, PENDING_COUNT_PATH)?;
+        Ok(count.unwrap_or_default())
+    }
+
+    fn take_events_after(&mut self, count: usize) -> Result<Vec<Event>, ()> {
+        let total = self.pending_events()?;
+        let mut events = Vec::default();
+        for index in count..total {
+            let path = event_path(index);
+            let event = <R as Database<Bincode>>::get::<Event>(self, &path)?.ok_or(())?;
+            self.store_delete(&path)?;
+            events.push(event);
+        }
+
+        match count {
+            0 => self.store_delete(PENDING_EVENTS_PATH)?,
+            _ if total > count => {
+                <R as Database<Bincode>>::save(self, PENDING_COUNT_PATH, &count)?;
+            }
+            _ => {}
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Events};
+    use crate::core::MockRuntime;
+
+    #[test]
+    fn test_emit_and_take() {
+        let mut runtime = MockRuntime::default();
+        assert_eq!(runtime.take_events(), Ok(Vec::default()));
+
+        runtime.emit("minted", &42).unwrap();
+        runtime.emit("burnt", &vec!["a", "b"]).unwrap();
+
+        let events = runtime.take_events().unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    name: "minted".to_string(),
+                    data: "42".to_string()
+                },
+                Event {
+                    name: "burnt".to_string(),
+                    data: "[\"a\",\"b\"]".to_string()
+                }
+            ]
+        );
+        assert_eq!(runtime.take_events(), Ok(Vec::default()));
+    }
+
+    #[test]
+    fn test_take_events_after() {
+        let mut runtime = MockRuntime::default();
+        runtime.emit("deposited", &1).unwrap();
+        assert_eq!(runtime.pending_events(), Ok(1));
+
+        runtime.emit("minted", &2).unwrap();
+        runtime.emit("burnt", &3).unwrap();
+        let events = runtime.take_events_after(1).unwrap();
+        assert_eq!(
+            events.iter().map(|event| &event.name).collect::<Vec<_>>(),
+            vec!["minted", "burnt"]
+        );
+        assert_eq!(runtime.take_events_after(1), Ok(Vec::default()));
+
+        let events = runtime.take_events().unwrap();
+        assert_eq!(
+            events.iter().map(|event| &event.name).collect::<Vec<_>>(),
+            vec!["deposited"]
+        );
+    }
+
+    #[test]
+    fn test_many_events() {
+        let mut runtime = MockRuntime::default();
+        let data = "a".repeat(100);
+        for _ in 0..100 {
+            runtime.emit("minted", &data).unwrap();
+        }
+        assert_eq!(runtime.pending_events(), Ok(100));
+
+        let events = runtime.take_events().unwrap();
+        assert_eq!(events.len(), 100);
+        assert_eq!(runtime.pending_events(), Ok(0));
+    }
+}
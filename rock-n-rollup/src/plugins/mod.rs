@@ -9,3 +9,9 @@ pub mod database;
 pub mod dac;
 
 pub mod installer;
+
+pub mod multisig;
+
+pub mod events;
+
+pub mod outbox;
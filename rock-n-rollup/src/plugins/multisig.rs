@@ -0,0 +1,369 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::core::{FromInput, Input, Runtime};
+use crate::encoding::address::SmartRollupAddress;
+use crate::plugins::{
+    crypto::{PublicKey, Signature, Verifier},
+    database::{Backend, Bincode, Database},
+};
+
+/// Prefix of the signed bytes, so a signature of the committee cannot be used for another purpose
+const MULTISIG_TAG: &[u8] = b"rock-n-rollup:multisig";
+
+/// Path of the committee in the durable storage
+const COMMITTEE_PATH: &str = "/multisig/committee";
+
+/// Path of the nonce of the last approved message
+const NONCE_PATH: &str = "/multisig/nonce";
+
+/// Set of public keys allowed to approve a message
+///
+/// A message is approved when at least `threshold` members signed it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Committee {
+    threshold: usize,
+    members: Vec<PublicKey>,
+}
+
+impl Committee {
+    /// Creates a M-of-N committee
+    ///
+    /// The threshold should be between 1 and the number of members, and the members should be distinct
+    pub fn new(threshold: usize, members: Vec<PublicKey>) -> Result<Self, ()> {
+        if threshold == 0 || threshold > members.len() {
+            return Err(());
+        }
+        let has_duplicates = members
+            .iter()
+            .enumerate()
+            .any(|(i, member)| members[..i].contains(member));
+        if has_duplicates {
+            return Err(());
+        }
+        Ok(Committee { threshold, members })
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn members(&self) -> &[PublicKey] {
+        &self.members
+    }
+}
+
+/// Message signed by the members of the committee
+///
+/// Each signature is associated to the index of the member in the committee
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MultisigMessage {
+    nonce: u64,
+    payload: Vec<u8>,
+    signatures: Vec<(u32, Signature)>,
+}
+
+impl MultisigMessage {
+    /// Creates an unsigned message, the payload is encoded with bincode
+    ///
+    /// The nonce should be the nonce of the last approved message plus one
+    pub fn new<T: Serialize>(nonce: u64, payload: &T) -> Result<Self, ()> {
+        Ok(MultisigMessage {
+            nonce,
+            payload: Bincode::to_bytes(payload)?,
+            signatures: Vec::default(),
+        })
+    }
+
+    /// The data the members have to sign: `{tag}{rollup address}{nonce}{payload}`
+    ///
+    /// The message is only valid for the given rollup
+    pub fn signed_bytes(&self, rollup: &SmartRollupAddress) -> Vec<u8> {
+        let mut bytes = MULTISIG_TAG.to_vec();
+        bytes.extend(rollup.as_bytes());
+        bytes.extend(self.nonce.to_be_bytes());
+        bytes.extend(&self.payload);
+        bytes
+    }
+
+    /// Adds the signature of the member at the given index of the committee
+    pub fn add_signature(&mut self, member: u32, signature: Signature) -> &mut Self {
+        self.signatures.push((member, signature));
+        self
+    }
+
+    /// Encodes the message, as expected by the Approved extractor
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ()> {
+        Bincode::to_bytes(self)
+    }
+}
+
+pub trait Multisig {
+    /// Saves the committee in the durable storage
+    fn set_committee(&mut self, committee: &Committee) -> Result<(), ()>;
+
+    /// Reads the committee from the durable storage
+    fn committee(&mut self) -> Result<Option<Committee>, ()>;
+
+    /// Verifies that the message is approved by the committee
+    ///
+    /// The message should carry at least `threshold` valid signatures of distinct members,
+    /// and its nonce should follow the nonce of the last approved message.
+    /// When the message is approved, its nonce is saved so it cannot be replayed.
+    ///
+    /// Returns the members who signed the message
+    fn verify_multisig(&mut self, message: &MultisigMessage) -> Result<Vec<PublicKey>, ()>;
+}
+
+impl<R> Multisig for R
+where
+    R: Runtime,
+{
+    fn set_committee(&mut self, committee: &Committee) -> Result<(), ()> {
+        <R as Database<Bincode>>::save(self, COMMITTEE_PATH, committee).map(|_| ())
+    }
+
+    fn committee(&mut self) -> Result<Option<Committee>, ()> {
+        <R as Database<Bincode>>::get(self, COMMITTEE_PATH)
+    }
+
+    fn verify_multisig(&mut self, message: &MultisigMessage) -> Result<Vec<PublicKey>, ()> {
+        let committee = self.committee()?.ok_or(())?;
+
+        let last_nonce =
+            <R as Database<Bincode>>::get::<u64>(self, NONCE_PATH)?.unwrap_or_default();
+        if last_nonce.checked_add(1) != Some(message.nonce) {
+            return Err(());
+        }
+
+        let data = message.signed_bytes(&self.rollup_address()?);
+        let mut signers = Vec::<u32>::default();
+        for (member, signature) in &message.signatures {
+            // A member is counted only once
+            if signers.contains(member) {
+                continue;
+            }
+            let public_key = match committee.members.get(*member as usize) {
+                Some(public_key) => public_key,
+                None => continue,
+            };
+            if self.verify_signature(signature, public_key, &data) {
+                signers.push(*member);
+            }
+        }
+
+        if signers.len() < committee.threshold {
+            return Err(());
+        }
+
+        <R as Database<Bincode>>::save(self, NONCE_PATH, &message.nonce)?;
+
+        Ok(signers
+            .iter()
+            .map(|member| committee.members[*member as usize].clone())
+            .collect())
+    }
+}
+
+/// External message approved by the committee
+///
+/// The transition is executed only if the message is approved,
+/// the nonce is consumed when the message is extracted
+pub struct Approved<T>
+where
+    T: DeserializeOwned,
+{
+    payload: T,
+    signers: Vec<PublicKey>,
+}
+
+impl<T> Approved<T>
+where
+    T: DeserializeOwned,
+{
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+
+    /// The members of the committee who signed the message
+    pub fn signers(&self) -> &[PublicKey] {
+        &self.signers
+    }
+}
+
+impl<T, S> FromInput<Vec<u8>, S> for Approved<T>
+where
+    T: DeserializeOwned,
+{
+    fn from_input<R: Runtime>(runtime: &mut R, input: &Input<Vec<u8>>, _: &S) -> Result<Self, ()> {
+        match input.payload.as_slice() {
+            [0x01, message @ ..] => {
+                let message = Bincode::from_bytes::<MultisigMessage>(message)?;
+                // The payload is decoded before consuming the nonce
+                let payload = Bincode::from_bytes::<T>(&message.payload)?;
+                let signers = runtime.verify_multisig(&message)?;
+                Ok(Approved { payload, signers })
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Approved, Committee, Multisig, MultisigMessage, NONCE_PATH};
+    use crate::core::{Application, MockRuntime, Runtime};
+    use crate::encoding::address::SmartRollupAddress;
+    use crate::plugins::{
+        crypto::{SecretKey, Signer},
+        database::{Bincode, Database},
+    };
+
+    #[derive(Serialize, Deserialize)]
+    enum AdminAction {
+        Upgrade(Vec<u8>),
+    }
+
+    fn members() -> Vec<SecretKey> {
+        (1..=3).map(|i| SecretKey::from_seed([i; 32])).collect()
+    }
+
+    fn setup(runtime: &mut MockRuntime) -> Vec<SecretKey> {
+        let members = members();
+        let committee =
+            Committee::new(2, members.iter().map(|sk| sk.public_key()).collect()).unwrap();
+        runtime.set_committee(&committee).unwrap();
+        members
+    }
+
+    fn sign(
+        runtime: &mut MockRuntime,
+        nonce: u64,
+        members: &[(u32, &SecretKey)],
+    ) -> MultisigMessage {
+        let rollup = runtime.rollup_address().unwrap();
+        sign_for(runtime, &rollup, nonce, members)
+    }
+
+    fn sign_for(
+        runtime: &mut MockRuntime,
+        rollup: &SmartRollupAddress,
+        nonce: u64,
+        members: &[(u32, &SecretKey)],
+    ) -> MultisigMessage {
+        let mut message = MultisigMessage::new(nonce, &AdminAction::Upgrade(vec![1, 2])).unwrap();
+        let data = message.signed_bytes(rollup);
+        for (index, secret_key) in members {
+            let signature = runtime.sign(secret_key, &data);
+            message.add_signature(*index, signature);
+        }
+        message
+    }
+
+    #[test]
+    fn test_invalid_committee() {
+        let members = members()
+            .iter()
+            .map(|sk| sk.public_key())
+            .collect::<Vec<_>>();
+
+        assert!(Committee::new(0, members.clone()).is_err());
+        assert!(Committee::new(4, members.clone()).is_err());
+        assert!(Committee::new(1, vec![members[0].clone(), members[0].clone()]).is_err());
+    }
+
+    #[test]
+    fn test_threshold() {
+        let mut runtime = MockRuntime::default();
+        let members = setup(&mut runtime);
+
+        let message = sign(&mut runtime, 1, &[(0, &members[0])]);
+        assert!(runtime.verify_multisig(&message).is_err());
+
+        let message = sign(&mut runtime, 1, &[(0, &members[0]), (2, &members[2])]);
+        let signers = runtime.verify_multisig(&message).unwrap();
+        assert_eq!(
+            signers,
+            vec![members[0].public_key(), members[2].public_key()]
+        );
+    }
+
+    #[test]
+    fn test_distinct_signatures() {
+        let mut runtime = MockRuntime::default();
+        let members = setup(&mut runtime);
+
+        // The same member signed twice
+        let message = sign(&mut runtime, 1, &[(0, &members[0]), (0, &members[0])]);
+        assert!(runtime.verify_multisig(&message).is_err());
+
+        // The second signature is not the one of the member
+        let message = sign(&mut runtime, 1, &[(0, &members[0]), (1, &members[0])]);
+        assert!(runtime.verify_multisig(&message).is_err());
+
+        // Unknown member
+        let message = sign(&mut runtime, 1, &[(0, &members[0]), (3, &members[1])]);
+        assert!(runtime.verify_multisig(&message).is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut runtime = MockRuntime::default();
+        let members = setup(&mut runtime);
+
+        let message = sign(&mut runtime, 1, &[(0, &members[0]), (1, &members[1])]);
+        assert!(runtime.verify_multisig(&message).is_ok());
+        assert!(runtime.verify_multisig(&message).is_err());
+
+        let message = sign(&mut runtime, 3, &[(0, &members[0]), (1, &members[1])]);
+        assert!(runtime.verify_multisig(&message).is_err());
+
+        let message = sign(&mut runtime, 2, &[(0, &members[0]), (1, &members[1])]);
+        assert!(runtime.verify_multisig(&message).is_ok());
+
+        // No message can follow the last nonce
+        <MockRuntime as Database<Bincode>>::save(&mut runtime, NONCE_PATH, &u64::MAX).unwrap();
+        let message = sign(&mut runtime, 0, &[(0, &members[0]), (1, &members[1])]);
+        assert!(runtime.verify_multisig(&message).is_err());
+    }
+
+    #[test]
+    fn test_other_rollup() {
+        let mut runtime = MockRuntime::default();
+        let members = setup(&mut runtime);
+
+        // The message has been approved for another rollup with the same committee
+        let other = SmartRollupAddress::from([1; 20]);
+        let message = sign_for(
+            &mut runtime,
+            &other,
+            1,
+            &[(0, &members[0]), (1, &members[1])],
+        );
+        assert!(runtime.verify_multisig(&message).is_err());
+    }
+
+    fn upgrade<R: Runtime>(rt: &mut R, action: Approved<AdminAction>) {
+        let AdminAction::Upgrade(kernel) = action.payload();
+        rt.write_debug(&format!("upgrade {:?}", kernel));
+    }
+
+    #[test]
+    fn test_approved_extractor() {
+        let mut runtime = MockRuntime::default();
+        let members = setup(&mut runtime);
+
+        let approved = sign(&mut runtime, 1, &[(0, &members[0]), (1, &members[1])]);
+        let rejected = sign(&mut runtime, 2, &[(0, &members[0])]);
+        for message in [rejected, approved] {
+            let mut bytes = vec![0x01];
+            bytes.extend(message.to_bytes().unwrap());
+            runtime.add_input(bytes);
+        }
+
+        Application::new(&mut runtime).register(upgrade).run();
+
+        assert_eq!(runtime.stdout(), vec!["upgrade [1, 2]"]);
+    }
+}
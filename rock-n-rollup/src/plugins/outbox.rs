@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::Runtime;
+use crate::plugins::database::{Bincode, Database};
+
+/// Path of the number of messages written to the outbox at the last level
+const OUTBOX_COUNTER_PATH: &str = "/outbox/counter";
+
+/// Position of a message in the outbox
+///
+/// It is needed to execute the message on L1, once the commitment of its level is cemented
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxMessageId {
+    pub level: u32,
+    pub index: u32,
+}
+
+pub trait Outbox {
+    /// Writes a message to the outbox of the given level, the level of the input being processed
+    ///
+    /// Returns the position of the message in the outbox
+    fn write_outbox_message(&mut self, level: u32, message: &[u8]) -> Result<OutboxMessageId, ()>;
+}
+
+impl<R> Outbox for R
+where
+    R: Runtime,
+{
+    fn write_outbox_message(&mut self, level: u32, message: &[u8]) -> Result<OutboxMessageId, ()> {
+        let index =
+            match <R as Database<Bincode>>::get::<OutboxMessageId>(self, OUTBOX_COUNTER_PATH)? {
+                Some(last) if last.level == level => last.index + 1,
+                _ => 0,
+            };
+        self.write_output(message)?;
+        let id = OutboxMessageId { level, index };
+        <R as Database<Bincode>>::save(self, OUTBOX_COUNTER_PATH, &id)?;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Outbox, OutboxMessageId};
+    use crate::core::MockRuntime;
+
+    #[test]
+    fn test_outbox_message_id() {
+        let mut runtime = MockRuntime::default();
+
+        let ids = [(1, [1]), (1, [2]), (2, [3])]
+            .iter()
+            .map(|(level, message)| runtime.write_outbox_message(*level, message).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                OutboxMessageId { level: 1, index: 0 },
+                OutboxMessageId { level: 1, index: 1 },
+                OutboxMessageId { level: 2, index: 0 },
+            ]
+        );
+        assert_eq!(runtime.outputs(), &[vec![1], vec![2], vec![3]]);
+
+        // The message is too large
+        assert!(runtime.write_outbox_message(2, &[0; 5000]).is_err());
+        assert_eq!(runtime.outputs().len(), 3);
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::Runtime,
+    encoding::address::ImplicitAddress,
+    plugins::{
+        crypto::PublicKey,
+        database::{Bincode, Database},
+    },
+};
+
+/// Kind of the operations updating the keys of an account
+///
+/// These operations are applied by the operation service, they are not processed by the transitions
+pub const KEYS_KIND: u8 = 0xFF;
+
+/// Key allowed to sign some kinds of operations on behalf of an account, until a given level
+///
+/// A session key cannot update the keys of the account
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionKey {
+    pub public_key: PublicKey,
+    /// Last level at which the key can be used
+    pub expires_at: u32,
+    /// Kinds of the operations the key can sign
+    pub kinds: Vec<u8>,
+}
+
+/// Update of the keys of an account, payload of the operations of kind KEYS_KIND
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyUpdate {
+    AddKey(PublicKey),
+    RevokeKey(PublicKey),
+    /// Adds a session key, or replaces the session key with the same public key
+    AddSessionKey(SessionKey),
+    RevokeSessionKey(PublicKey),
+}
+
+/// Keys of an account
+///
+/// The address of the account does not change when its keys are updated
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountKeys {
+    keys: Vec<PublicKey>,
+    sessions: Vec<SessionKey>,
+}
+
+impl AccountKeys {
+    /// Keys of an account which never updated its keys: the key its address is derived from
+    fn initial(public_key: &PublicKey) -> Self {
+        AccountKeys {
+            keys: vec![public_key.clone()],
+            sessions: Vec::default(),
+        }
+    }
+
+    /// The keys allowed to sign any operation
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    pub fn session_keys(&self) -> &[SessionKey] {
+        &self.sessions
+    }
+
+    /// Returns true if the key can sign an operation of the given kind at the given level
+    pub fn is_authorized(&self, public_key: &PublicKey, kind: u8, level: u32) -> bool {
+        if self.keys.contains(public_key) {
+            return true;
+        }
+        self.sessions.iter().any(|session| {
+            &session.public_key == public_key
+                && kind != KEYS_KIND
+                && level <= session.expires_at
+                && session.kinds.contains(&kind)
+        })
+    }
+
+    /// Applies the update, the expired session keys are removed
+    ///
+    /// An account keeps at least one key
+    pub fn apply(&mut self, update: &KeyUpdate, level: u32) -> Result<(), ()> {
+        self.sessions.retain(|session| level <= session.expires_at);
+
+        match update {
+            KeyUpdate::AddKey(public_key) => {
+                if self.keys.contains(public_key) {
+                    return Err(());
+                }
+                self.sessions
+                    .retain(|session| &session.public_key != public_key);
+                self.keys.push(public_key.clone());
+            }
+            KeyUpdate::RevokeKey(public_key) => {
+                let size = self.keys.len();
+                self.keys.retain(|key| key != public_key);
+                if self.keys.len() == size || self.keys.is_empty() {
+                    return Err(());
+                }
+            }
+            KeyUpdate::AddSessionKey(session) => {
+                if self.keys.contains(&session.public_key) || session.expires_at < level {
+                    return Err(());
+                }
+                self.sessions
+                    .retain(|previous| previous.public_key != session.public_key);
+                self.sessions.push(session.clone());
+            }
+            KeyUpdate::RevokeSessionKey(public_key) => {
+                let size = self.sessions.len();
+                self.sessions
+                    .retain(|session| &session.public_key != public_key);
+                if self.sessions.len() == size {
+                    return Err(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn keys_path(account: &ImplicitAddress) -> String {
+    format!("/accounts/{}/keys", account)
+}
+
+pub trait Accounts {
+    /// Reads the keys of an account, None if the account never updated its keys
+    fn account_keys(&mut self, account: &ImplicitAddress) -> Result<Option<AccountKeys>, ()>;
+
+    /// Returns true if the key can sign an operation of the given kind at the given level on behalf of the account
+    ///
+    /// Until the account updates its keys, only the key its address is derived from is authorized
+    fn is_authorized(
+        &mut self,
+        account: &ImplicitAddress,
+        public_key: &PublicKey,
+        kind: u8,
+        level: u32,
+    ) -> Result<bool, ()>;
+
+    /// Applies an update of the keys of the account, signed by one of its keys
+    fn update_keys(
+        &mut self,
+        account: &ImplicitAddress,
+        signer: &PublicKey,
+        update: &KeyUpdate,
+        level: u32,
+    ) -> Result<(), ()>;
+}
+
+impl<R> Accounts for R
+where
+    R: Runtime,
+{
+    fn account_keys(&mut self, account: &ImplicitAddress) -> Result<Option<AccountKeys>, ()> {
+        <R as Database<Bincode>>::get(self, &keys_path(account))
+    }
+
+    fn is_authorized(
+        &mut self,
+        account: &ImplicitAddress,
+        public_key: &PublicKey,
+        kind: u8,
+        level: u32,
+    ) -> Result<bool, ()> {
+        match self.account_keys(account)? {
+            Some(keys) => Ok(keys.is_authorized(public_key, kind, level)),
+            None => Ok(&ImplicitAddress::from(public_key) == account),
+        }
+    }
+
+    fn update_keys(
+        &mut self,
+        account: &ImplicitAddress,
+        signer: &PublicKey,
+        update: &KeyUpdate,
+        level: u32,
+    ) -> Result<(), ()> {
+        if !self.is_authorized(account, signer, KEYS_KIND, level)? {
+            return Err(());
+        }
+        let mut keys = self
+            .account_keys(account)?
+            .unwrap_or_else(|| AccountKeys::initial(signer));
+        keys.apply(update, level)?;
+        <R as Database<Bincode>>::save(self, &keys_path(account), &keys).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Accounts, KeyUpdate, SessionKey, KEYS_KIND};
+    use crate::core::MockRuntime;
+    use crate::services::operation::TestAccount;
+
+    #[test]
+    fn test_rotate_key() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice");
+        let new_key = TestAccount::new("alice-2").public_key();
+        let account = alice.address();
+
+        assert_eq!(
+            runtime.is_authorized(&account, &alice.public_key(), 0, 0),
+            Ok(true)
+        );
+        assert_eq!(runtime.is_authorized(&account, &new_key, 0, 0), Ok(false));
+
+        // Only the keys of the account can update its keys
+        let update = KeyUpdate::AddKey(new_key.clone());
+        assert!(runtime.update_keys(&account, &new_key, &update, 0).is_err());
+        runtime
+            .update_keys(&account, &alice.public_key(), &update, 0)
+            .unwrap();
+
+        let update = KeyUpdate::RevokeKey(alice.public_key());
+        runtime.update_keys(&account, &new_key, &update, 0).unwrap();
+
+        assert_eq!(
+            runtime.is_authorized(&account, &alice.public_key(), 0, 0),
+            Ok(false)
+        );
+        assert_eq!(runtime.is_authorized(&account, &new_key, 0, 0), Ok(true));
+
+        // The last key cannot be revoked
+        let update = KeyUpdate::RevokeKey(new_key.clone());
+        assert!(runtime.update_keys(&account, &new_key, &update, 0).is_err());
+    }
+
+    #[test]
+    fn test_session_key() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice");
+        let session_key = TestAccount::new("alice-session").public_key();
+        let account = alice.address();
+
+        let update = KeyUpdate::AddSessionKey(SessionKey {
+            public_key: session_key.clone(),
+            expires_at: 10,
+            kinds: vec![1, 2],
+        });
+        runtime
+            .update_keys(&account, &alice.public_key(), &update, 5)
+            .unwrap();
+
+        assert_eq!(
+            runtime.is_authorized(&account, &session_key, 1, 10),
+            Ok(true)
+        );
+        // Expired session key
+        assert_eq!(
+            runtime.is_authorized(&account, &session_key, 1, 11),
+            Ok(false)
+        );
+        // Kind not allowed
+        assert_eq!(
+            runtime.is_authorized(&account, &session_key, 3, 10),
+            Ok(false)
+        );
+        // A session key cannot update the keys
+        assert_eq!(
+            runtime.is_authorized(&account, &session_key, KEYS_KIND, 10),
+            Ok(false)
+        );
+
+        let update = KeyUpdate::RevokeSessionKey(session_key.clone());
+        runtime
+            .update_keys(&account, &alice.public_key(), &update, 6)
+            .unwrap();
+        assert_eq!(
+            runtime.is_authorized(&account, &session_key, 1, 6),
+            Ok(false)
+        );
+    }
+}
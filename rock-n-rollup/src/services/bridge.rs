@@ -0,0 +1,727 @@
+use blake2::digest::{Update, VariableOutput};
+use blake2::Blake2bVar;
+use serde::{Deserialize, Serialize};
+use tezos_data_encoding::{enc::BinWriter, types::Zarith};
+use tezos_smart_rollup_encoding::{
+    contract::Contract,
+    entrypoint::Entrypoint,
+    michelson::{
+        ticket::Ticket, Michelson, MichelsonBytes, MichelsonContract, MichelsonNat,
+        MichelsonOption, MichelsonPair,
+    },
+    outbox::{OutboxMessage, OutboxMessageTransaction},
+};
+
+use crate::{
+    core::{Input, Runtime, MAX_OUTPUT_SIZE},
+    encoding::{
+        address::{ContractAddress, ImplicitAddress, SmartRollupAddress},
+        b58::B58Check,
+    },
+    plugins::{
+        database::{Backend, Bincode, Database},
+        events::Events,
+        logger::Logger,
+        outbox::{Outbox, OutboxMessageId},
+    },
+    services::{
+        internal::{Internal, Transfer},
+        ledger::Ledger,
+        operation::{RawExternalOperation, Signed, UnsignedOperation},
+    },
+};
+
+/// Kind of the withdrawal operations
+pub const WITHDRAWAL_KIND: u8 = 0xFD;
+
+/// Parameter of a deposit: the receiver on the rollup and the deposited ticket
+///
+/// In Michelson: `pair address (ticket bytes)`
+pub type DepositParameter = MichelsonPair<MichelsonContract, Ticket<MichelsonBytes>>;
+
+/// Contents of the FA2.1 tickets: the token id and its metadata
+///
+/// In Michelson: `pair nat (option bytes)`
+pub type Fa2Contents = MichelsonPair<MichelsonNat, MichelsonOption<MichelsonBytes>>;
+
+/// Parameter of a deposit of FA2.1 tickets
+///
+/// In Michelson: `pair address (ticket (pair nat (option bytes)))`
+pub type Fa2DepositParameter = MichelsonPair<MichelsonContract, Ticket<Fa2Contents>>;
+
+/// Decoded contents of a ticket
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TicketContents {
+    /// Contents of type `bytes`
+    Bytes(Vec<u8>),
+    /// Contents of type `pair nat (option bytes)`, see `Fa2Contents`
+    ///
+    /// The token id is a nat of any size, it is written in decimal
+    Fa2 {
+        token_id: String,
+        metadata: Option<Vec<u8>>,
+    },
+}
+
+/// Michelson types of the contents of the tickets supported by the bridge
+pub trait BridgeContents: Michelson + Sized {
+    /// Decodes the contents
+    fn to_contents(&self) -> Result<TicketContents, ()>;
+
+    /// Encodes the contents, returns None if they are of another type
+    fn from_contents(contents: &TicketContents) -> Option<Self>;
+}
+
+impl BridgeContents for MichelsonBytes {
+    fn to_contents(&self) -> Result<TicketContents, ()> {
+        let MichelsonBytes(bytes) = self;
+        Ok(TicketContents::Bytes(bytes.clone()))
+    }
+
+    fn from_contents(contents: &TicketContents) -> Option<Self> {
+        match contents {
+            TicketContents::Bytes(bytes) => Some(MichelsonBytes(bytes.clone())),
+            TicketContents::Fa2 { .. } => None,
+        }
+    }
+}
+
+impl BridgeContents for Fa2Contents {
+    fn to_contents(&self) -> Result<TicketContents, ()> {
+        let MichelsonPair(MichelsonNat(Zarith(token_id)), MichelsonOption(metadata)) = self;
+        let token_id = token_id.to_string();
+        let metadata = metadata
+            .as_ref()
+            .map(|MichelsonBytes(metadata)| metadata.clone());
+        Ok(TicketContents::Fa2 { token_id, metadata })
+    }
+
+    fn from_contents(contents: &TicketContents) -> Option<Self> {
+        match contents {
+            TicketContents::Fa2 { token_id, metadata } => {
+                let token_id = MichelsonNat::new(Zarith(token_id.parse().ok()?))?;
+                let metadata = metadata.clone().map(MichelsonBytes);
+                let encoded = MichelsonPair(token_id, MichelsonOption(metadata));
+                // The token id should be written as it is decoded, e.g. without leading zeros
+                match encoded.to_contents() {
+                    Ok(decoded) if decoded == *contents => Some(encoded),
+                    _ => None,
+                }
+            }
+            TicketContents::Bytes(_) => None,
+        }
+    }
+}
+
+/// Ticket identified by its ticketer and its contents
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketId {
+    pub ticketer: ContractAddress,
+    pub contents: TicketContents,
+}
+
+impl TicketId {
+    /// Identifies a ticket received from L1
+    pub fn from_ticket<C: BridgeContents>(ticket: &Ticket<C>) -> Result<TicketId, ()> {
+        let ticketer =
+            ContractAddress::from_b58check(&ticket.creator().0.to_b58check()).map_err(|_| ())?;
+        let contents = ticket.contents().to_contents()?;
+        Ok(TicketId { ticketer, contents })
+    }
+
+    /// Rebuilds the ticket with the given amount, to send it back to L1
+    pub fn to_ticket<C: BridgeContents>(&self, amount: u64) -> Result<Ticket<C>, ()> {
+        let ticketer = Contract::from_b58check(&self.ticketer.to_b58check()).map_err(|_| ())?;
+        let contents = C::from_contents(&self.contents).ok_or(())?;
+        Ticket::new(ticketer, contents, amount).map_err(|_| ())
+    }
+
+    /// Name of the token of the ledger of the ticket
+    ///
+    /// It is derived from the blake2b 160 bits of the ticket id
+    pub fn token(&self) -> String {
+        // The type of the contents is encoded, tickets of different types never share a ledger
+        let contents = Bincode::to_bytes(&self.contents).unwrap();
+        let mut hasher = Blake2bVar::new(20).unwrap();
+        hasher.update(self.ticketer.to_b58check().as_bytes());
+        hasher.update(&contents);
+        let mut hash = [0u8; 20];
+        hasher.finalize_variable(&mut hash).unwrap();
+        format!("ticket-{}", hex::encode(hash))
+    }
+
+    /// Ledger of the deposited tickets
+    pub fn ledger(&self) -> Ledger {
+        Ledger::new(&self.token())
+    }
+}
+
+fn ticket_path(token: &str) -> String {
+    format!("/bridge/tickets/{}", token)
+}
+
+/// Reads the ticket of a ledger, known once the ticket has been deposited
+pub fn ticket_id<R: Runtime>(runtime: &mut R, token: &str) -> Result<Option<TicketId>, ()> {
+    <R as Database<Bincode>>::get(runtime, &ticket_path(token))
+}
+
+/// Bridge crediting the tickets deposited from L1
+///
+/// The deposited tickets are credited to the ledger of the ticket, see `TicketId::ledger`
+#[derive(Clone)]
+pub struct Bridge {
+    ticketers: Vec<ContractAddress>,
+}
+
+impl Bridge {
+    /// Credits the ticket of a deposit to its receiver
+    ///
+    /// The transfers to another rollup are rejected
+    fn deposit_transfer<R: Runtime, C: BridgeContents>(
+        &self,
+        runtime: &mut R,
+        transfer: &Transfer<MichelsonPair<MichelsonContract, Ticket<C>>>,
+    ) -> Result<(), ()> {
+        if transfer.destination() != &runtime.rollup_address()? {
+            runtime.warn(&format!(
+                "bridge: the transfer is for another rollup {}",
+                transfer.destination()
+            ));
+            return Err(());
+        }
+
+        let MichelsonPair(MichelsonContract(receiver), ticket) = transfer.payload();
+
+        let ticket_id = TicketId::from_ticket(ticket);
+        let receiver = ImplicitAddress::from_b58check(&receiver.to_b58check());
+        let amount = u64::try_from(ticket.amount());
+
+        match (ticket_id, receiver, amount) {
+            (Ok(ticket_id), Ok(receiver), Ok(amount)) => {
+                self.deposit(runtime, &ticket_id, &receiver, amount)
+            }
+            (Err(_), _, _) => {
+                runtime.warn("bridge: the ticketer is not a contract");
+                Err(())
+            }
+            (_, Err(_), _) => {
+                runtime.warn("bridge: the receiver is not an implicit account");
+                Err(())
+            }
+            (_, _, Err(_)) => {
+                runtime.warn("bridge: the amount of the ticket is too large");
+                Err(())
+            }
+        }
+    }
+
+    /// Credits the deposited ticket to the receiver
+    fn deposit<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        ticket_id: &TicketId,
+        receiver: &ImplicitAddress,
+        amount: u64,
+    ) -> Result<(), ()> {
+        if !self.ticketers.contains(&ticket_id.ticketer) {
+            runtime.warn(&format!("bridge: unknown ticketer {}", ticket_id.ticketer));
+            return Err(());
+        }
+
+        let token = ticket_id.token();
+        let path = ticket_path(&token);
+        if <R as Database<Bincode>>::get::<TicketId>(runtime, &path)?.is_none() {
+            <R as Database<Bincode>>::save(runtime, &path, ticket_id)?;
+        }
+        ticket_id.ledger().mint(runtime, receiver, amount)
+    }
+}
+
+#[rock_n_rollup::service]
+impl Bridge {
+    /// Creates the bridge accepting the tickets of the given KT1 contracts
+    ///
+    /// Panics if an address is not a valid contract address
+    pub fn new(ticketers: &[&str]) -> Self {
+        let ticketers = ticketers
+            .iter()
+            .map(|ticketer| ContractAddress::from_b58check(ticketer).unwrap())
+            .collect();
+        Self { ticketers }
+    }
+
+    #[transition]
+    fn deposit_ticket<R: Runtime>(
+        rt: &mut R,
+        transfer: Internal<Transfer<DepositParameter>>,
+        state: Bridge,
+    ) -> Result<(), ()> {
+        state.deposit_transfer(rt, transfer.payload())
+    }
+
+    #[transition]
+    fn deposit_fa2_ticket<R: Runtime>(
+        rt: &mut R,
+        transfer: Internal<Transfer<Fa2DepositParameter>>,
+        state: Bridge,
+    ) -> Result<(), ()> {
+        state.deposit_transfer(rt, transfer.payload())
+    }
+}
+
+/// Withdrawal of deposited tickets, payload of the operations of kind WITHDRAWAL_KIND encoded with bincode
+///
+/// The tickets are sent to a contract on L1, by calling the given entrypoint with the ticket as parameter
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Withdrawal {
+    pub ticket: TicketId,
+    pub amount: u64,
+    /// Contract receiving the tickets, the ticketer if None
+    pub receiver: Option<ContractAddress>,
+    pub entrypoint: String,
+}
+
+impl Withdrawal {
+    /// Creates the operation to sign on behalf of the account
+    pub fn unsigned_operation(
+        &self,
+        destination: SmartRollupAddress,
+        account: ImplicitAddress,
+        nonce: u64,
+    ) -> Result<UnsignedOperation, ()> {
+        let payload = Bincode::to_bytes(self)?;
+        Ok(UnsignedOperation {
+            kind: WITHDRAWAL_KIND,
+            ..UnsignedOperation::new(destination, account, nonce, &payload)
+        })
+    }
+
+    /// Encodes the outbox message calling the receiver with the ticket
+    fn outbox_message(&self) -> Result<Vec<u8>, ()> {
+        match self.ticket.contents {
+            TicketContents::Bytes(_) => self.encode_outbox_message::<MichelsonBytes>(),
+            TicketContents::Fa2 { .. } => self.encode_outbox_message::<Fa2Contents>(),
+        }
+    }
+
+    fn encode_outbox_message<C: BridgeContents>(&self) -> Result<Vec<u8>, ()> {
+        let receiver = self.receiver.as_ref().unwrap_or(&self.ticket.ticketer);
+        let destination = Contract::from_b58check(&receiver.to_b58check()).map_err(|_| ())?;
+        let ticket = self.ticket.to_ticket::<C>(self.amount)?;
+        let entrypoint = Entrypoint::try_from(self.entrypoint.clone()).map_err(|_| ())?;
+
+        let transaction = OutboxMessageTransaction {
+            parameters: ticket,
+            destination,
+            entrypoint,
+        };
+        let message = OutboxMessage::AtomicTransactionBatch(vec![transaction].into());
+        let mut bytes = Vec::default();
+        message.bin_write(&mut bytes).map_err(|_| ())?;
+        Ok(bytes)
+    }
+}
+
+#[derive(Serialize)]
+struct Withdrawn<'a> {
+    token: &'a str,
+    from: &'a ImplicitAddress,
+    amount: u64,
+    outbox: OutboxMessageId,
+}
+
+/// Writes the outbox message sending the withdrawn tickets to L1, and burns them
+///
+/// The balance and the size of the message are checked first, so the tickets are only burnt once sent.
+/// The position of the message in the outbox is emitted in the `withdrawn` event
+fn send_to_outbox<R: Runtime>(
+    runtime: &mut R,
+    source: &ImplicitAddress,
+    level: u32,
+    withdrawal: &Withdrawal,
+    message: &[u8],
+) -> Result<OutboxMessageId, ()> {
+    let ledger = withdrawal.ticket.ledger();
+    if message.len() > MAX_OUTPUT_SIZE || ledger.balance(runtime, source)? < withdrawal.amount {
+        return Err(());
+    }
+
+    let outbox = runtime.write_outbox_message(level, message)?;
+    ledger.burn(runtime, source, withdrawal.amount)?;
+    runtime.emit(
+        "withdrawn",
+        &Withdrawn {
+            token: ledger.token(),
+            from: source,
+            amount: withdrawal.amount,
+            outbox,
+        },
+    )?;
+    Ok(outbox)
+}
+
+/// Service processing the signed withdrawals of the deposited tickets
+#[derive(Clone, Default)]
+pub struct TicketWithdrawal;
+
+#[rock_n_rollup::service(payload = RawExternalOperation)]
+impl TicketWithdrawal {
+    pub fn new() -> Self {
+        TicketWithdrawal
+    }
+
+    #[guard]
+    fn is_withdrawal<R: Runtime>(_: &mut R, input: &Input<RawExternalOperation>) -> bool {
+        input.payload.kind() == WITHDRAWAL_KIND
+    }
+
+    #[transition]
+    fn withdraw<R: Runtime>(rt: &mut R, withdrawal: Signed<Withdrawal>) -> Result<(), ()> {
+        let source = withdrawal.source().clone();
+        let level = withdrawal.level();
+        let withdrawal = withdrawal.into_payload();
+
+        let message = withdrawal.outbox_message().map_err(|_| {
+            rt.warn("bridge: the withdrawal cannot be encoded");
+        })?;
+        send_to_outbox(rt, &source, level, &withdrawal, &message).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_data_encoding::{enc::BinWriter, types::Zarith};
+    use tezos_smart_rollup_encoding::{
+        contract::Contract,
+        entrypoint::Entrypoint,
+        michelson::{
+            ticket::Ticket, MichelsonBytes, MichelsonContract, MichelsonNat, MichelsonOption,
+            MichelsonPair,
+        },
+        outbox::{OutboxMessage, OutboxMessageTransaction},
+    };
+
+    use super::{
+        send_to_outbox, ticket_id, Bridge, Fa2Contents, TicketContents, TicketId, TicketWithdrawal,
+        Withdrawal,
+    };
+    use crate::core::{Application, MockRuntime, Runtime, MOCK_ROLLUP_ADDRESS};
+    use crate::encoding::{
+        address::{ContractAddress, ImplicitAddress, SmartRollupAddress},
+        b58::B58Check,
+    };
+    use crate::plugins::{events::Events, outbox::OutboxMessageId};
+    use crate::services::{
+        internal::transfer_message,
+        ledger::{Ledger, LedgerAction, LedgerOperation},
+        operation::{RawExternalOperation, TestAccount},
+        receipt::{Receipts, Status},
+    };
+
+    const TICKETER: &str = "KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ";
+
+    /// Transfer of the ticketer depositing tickets of contents `0x010203` to the receiver
+    fn deposit_message(receiver: &ImplicitAddress, amount: u64) -> Vec<u8> {
+        let receiver = Contract::from_b58check(&receiver.to_b58check()).unwrap();
+        let ticketer = Contract::from_b58check(TICKETER).unwrap();
+        let ticket = Ticket::new(ticketer, MichelsonBytes(vec![1, 2, 3]), amount).unwrap();
+        transfer_message(TICKETER, MichelsonPair(MichelsonContract(receiver), ticket))
+    }
+
+    fn deposited_ticket() -> TicketId {
+        TicketId {
+            ticketer: ContractAddress::from_b58check(TICKETER).unwrap(),
+            contents: TicketContents::Bytes(vec![1, 2, 3]),
+        }
+    }
+
+    fn sign_withdrawal(
+        runtime: &mut MockRuntime,
+        account: &TestAccount,
+        nonce: u64,
+        withdrawal: &Withdrawal,
+    ) -> RawExternalOperation {
+        let destination = runtime.rollup_address().unwrap();
+        let content = withdrawal
+            .unsigned_operation(destination, account.address(), nonce)
+            .unwrap();
+        account.sign(runtime, content)
+    }
+
+    #[test]
+    fn test_service() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice");
+        let ticket = deposited_ticket();
+        let withdrawal = Withdrawal {
+            ticket: ticket.clone(),
+            amount: 4,
+            receiver: None,
+            entrypoint: "withdraw".to_string(),
+        };
+        let operation = sign_withdrawal(&mut runtime, &alice, 1, &withdrawal);
+
+        // The inputs are read from the last one
+        runtime
+            .add_input_at_level(8, operation.to_bytes())
+            .add_input_at_level(7, deposit_message(&alice.address(), 10));
+
+        Application::new(&mut runtime)
+            .service(Bridge::new(&[TICKETER]))
+            .service(TicketWithdrawal::new())
+            .run();
+
+        let ledger = ticket.ledger();
+        assert_eq!(ledger.balance(&mut runtime, &alice.address()), Ok(6));
+        assert_eq!(ledger.total_supply(&mut runtime), Ok(6));
+        assert_eq!(runtime.outputs(), &[withdrawal.outbox_message().unwrap()]);
+
+        let hash = operation.hash(&mut runtime);
+        let receipt = runtime.receipt(&hash).unwrap().unwrap();
+        assert_eq!(receipt.status, Status::Applied);
+        assert_eq!(receipt.events.last().unwrap().name, "withdrawn");
+    }
+
+    #[test]
+    fn test_withdrawal_with_ledger() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+        let ticket = deposited_ticket();
+        let ledger = ticket.ledger();
+
+        // Alice sends some tickets to bob, who withdraws them
+        let transfer = LedgerOperation {
+            token: ledger.token().to_string(),
+            action: LedgerAction::Transfer {
+                to: bob.address(),
+                amount: 3,
+            },
+        };
+        let destination = runtime.rollup_address().unwrap();
+        let content = transfer
+            .unsigned_operation(destination, alice.address(), 1)
+            .unwrap();
+        let transfer = alice.sign(&mut runtime, content);
+        let withdrawal = Withdrawal {
+            ticket: ticket.clone(),
+            amount: 2,
+            receiver: None,
+            entrypoint: "withdraw".to_string(),
+        };
+        let withdrawal = sign_withdrawal(&mut runtime, &bob, 1, &withdrawal);
+
+        runtime
+            .add_input(RawExternalOperation::batch_to_bytes(&[
+                transfer.clone(),
+                withdrawal.clone(),
+            ]))
+            .add_input(deposit_message(&alice.address(), 10));
+
+        Application::new(&mut runtime)
+            .service(Bridge::new(&[TICKETER]))
+            .service(Ledger::new(ledger.token()))
+            .service(TicketWithdrawal::new())
+            .run();
+
+        assert_eq!(ledger.balance(&mut runtime, &alice.address()), Ok(7));
+        assert_eq!(ledger.balance(&mut runtime, &bob.address()), Ok(1));
+        assert_eq!(runtime.outputs().len(), 1);
+
+        // Each operation is verified once, and applied by its service
+        for operation in [transfer, withdrawal] {
+            let hash = operation.hash(&mut runtime);
+            let receipt = runtime.receipt(&hash).unwrap().unwrap();
+            assert_eq!(receipt.status, Status::Applied);
+        }
+    }
+
+    #[test]
+    fn test_ticket_token() {
+        let ticketer = ContractAddress::from_b58check(TICKETER).unwrap();
+        let ticket = |contents: TicketContents| TicketId {
+            ticketer: ticketer.clone(),
+            contents,
+        };
+        let bytes = |bytes: &[u8]| ticket(TicketContents::Bytes(bytes.to_vec()));
+        let fa2 = |token_id: &str| {
+            ticket(TicketContents::Fa2 {
+                token_id: token_id.to_string(),
+                metadata: None,
+            })
+        };
+
+        assert_eq!(bytes(&[1]).token(), bytes(&[1]).token());
+        assert_ne!(bytes(&[1]).token(), bytes(&[2]).token());
+        assert!(bytes(&[1]).token().starts_with("ticket-"));
+
+        assert_ne!(fa2("0").token(), fa2("1").token());
+        assert_ne!(fa2("0").token(), bytes(&[]).token());
+    }
+
+    /// Token id larger than a u64
+    const LARGE_TOKEN_ID: &str = "340282366920938463463374607431768211456";
+
+    fn fa2_ticket(token_id: &str, amount: u64) -> Ticket<Fa2Contents> {
+        let token_id = MichelsonNat::new(Zarith(token_id.parse().unwrap())).unwrap();
+        let contents = MichelsonPair(token_id, MichelsonOption(Some(MichelsonBytes(vec![7]))));
+        Ticket::new(Contract::from_b58check(TICKETER).unwrap(), contents, amount).unwrap()
+    }
+
+    fn fa2_ticket_id(token_id: &str) -> TicketId {
+        TicketId {
+            ticketer: ContractAddress::from_b58check(TICKETER).unwrap(),
+            contents: TicketContents::Fa2 {
+                token_id: token_id.to_string(),
+                metadata: Some(vec![7]),
+            },
+        }
+    }
+
+    #[test]
+    fn test_fa2_ticket_id() {
+        let ticket = fa2_ticket(LARGE_TOKEN_ID, 5);
+        let ticket_id = TicketId::from_ticket(&ticket).unwrap();
+        assert_eq!(ticket_id, fa2_ticket_id(LARGE_TOKEN_ID));
+
+        assert_eq!(ticket_id.to_ticket::<Fa2Contents>(5), Ok(ticket));
+        assert!(ticket_id.to_ticket::<MichelsonBytes>(5).is_err());
+
+        // The token id is not written as it is decoded
+        assert!(fa2_ticket_id("01").to_ticket::<Fa2Contents>(5).is_err());
+        assert!(fa2_ticket_id("-1").to_ticket::<Fa2Contents>(5).is_err());
+    }
+
+    #[test]
+    fn test_deposit_fa2_ticket() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice").address();
+        let receiver = Contract::from_b58check(&alice.to_b58check()).unwrap();
+        let parameter = MichelsonPair(MichelsonContract(receiver), fa2_ticket(LARGE_TOKEN_ID, 10));
+        runtime.add_input(transfer_message(TICKETER, parameter));
+
+        Application::new(&mut runtime)
+            .service(Bridge::new(&[TICKETER]))
+            .run();
+
+        let ticket = fa2_ticket_id(LARGE_TOKEN_ID);
+        let ledger = ticket.ledger();
+        assert_eq!(ledger.balance(&mut runtime, &alice), Ok(10));
+        assert_eq!(ticket_id(&mut runtime, ledger.token()), Ok(Some(ticket)));
+    }
+
+    #[test]
+    fn test_fa2_outbox_message() {
+        let receiver =
+            ContractAddress::from_b58check("KT1BRhcRAdLia3XQT1mPSofHyrmYpRddgj3s").unwrap();
+        let withdrawal = Withdrawal {
+            ticket: fa2_ticket_id(LARGE_TOKEN_ID),
+            amount: 3,
+            receiver: Some(receiver.clone()),
+            entrypoint: "burn".to_string(),
+        };
+
+        let transaction = OutboxMessageTransaction {
+            parameters: fa2_ticket(LARGE_TOKEN_ID, 3),
+            destination: Contract::from_b58check(&receiver.to_b58check()).unwrap(),
+            entrypoint: Entrypoint::try_from("burn".to_string()).unwrap(),
+        };
+        let message = OutboxMessage::AtomicTransactionBatch(vec![transaction].into());
+        let mut expected = Vec::default();
+        message.bin_write(&mut expected).unwrap();
+
+        assert_eq!(withdrawal.outbox_message(), Ok(expected));
+    }
+
+    #[test]
+    fn test_deposit_to_another_rollup() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice").address();
+
+        // The transfer targets the mock rollup, whereas the kernel runs another one
+        runtime
+            .set_rollup_address(SmartRollupAddress::from([1; 20]))
+            .add_input(deposit_message(&alice, 10));
+        Application::new(&mut runtime)
+            .service(Bridge::new(&[TICKETER]))
+            .run();
+
+        let ledger = deposited_ticket().ledger();
+        assert_eq!(ledger.balance(&mut runtime, &alice), Ok(0));
+        assert_eq!(ticket_id(&mut runtime, ledger.token()), Ok(None));
+        let warning = format!(
+            "[WARN] bridge: the transfer is for another rollup {}\n",
+            MOCK_ROLLUP_ADDRESS
+        );
+        assert!(runtime.stdout().contains(&warning.as_str()));
+    }
+
+    #[test]
+    fn test_deposit() {
+        let mut runtime = MockRuntime::default();
+        let bridge = Bridge::new(&[TICKETER]);
+        let alice = TestAccount::new("alice").address();
+        let ticket = TicketId {
+            ticketer: ContractAddress::from_b58check(TICKETER).unwrap(),
+            contents: TicketContents::Bytes(vec![1, 2, 3]),
+        };
+
+        bridge.deposit(&mut runtime, &ticket, &alice, 10).unwrap();
+        bridge.deposit(&mut runtime, &ticket, &alice, 5).unwrap();
+
+        let ledger = ticket.ledger();
+        assert_eq!(ledger.balance(&mut runtime, &alice), Ok(15));
+        assert_eq!(ticket_id(&mut runtime, ledger.token()), Ok(Some(ticket)));
+
+        // Unknown ticketer
+        let unknown = TicketId {
+            ticketer: ContractAddress::from_b58check("KT1BRhcRAdLia3XQT1mPSofHyrmYpRddgj3s")
+                .unwrap(),
+            contents: TicketContents::Bytes(vec![1, 2, 3]),
+        };
+        assert!(bridge.deposit(&mut runtime, &unknown, &alice, 10).is_err());
+        assert_eq!(unknown.ledger().balance(&mut runtime, &alice), Ok(0));
+        assert_eq!(
+            runtime.stdout().last(),
+            Some(&format!("[WARN] bridge: unknown ticketer {}\n", unknown.ticketer).as_str())
+        );
+    }
+
+    #[test]
+    fn test_send_to_outbox() {
+        let mut runtime = MockRuntime::default();
+        let bridge = Bridge::new(&[TICKETER]);
+        let alice = TestAccount::new("alice").address();
+        let ticket = TicketId {
+            ticketer: ContractAddress::from_b58check(TICKETER).unwrap(),
+            contents: TicketContents::Bytes(vec![1, 2, 3]),
+        };
+        bridge.deposit(&mut runtime, &ticket, &alice, 10).unwrap();
+        let _ = runtime.take_events();
+
+        let withdrawal = |amount| Withdrawal {
+            ticket: ticket.clone(),
+            amount,
+            receiver: None,
+            entrypoint: "withdraw".to_string(),
+        };
+
+        // More than the balance
+        assert!(send_to_outbox(&mut runtime, &alice, 7, &withdrawal(11), &[1]).is_err());
+        assert!(runtime.outputs().is_empty());
+
+        let outbox = send_to_outbox(&mut runtime, &alice, 7, &withdrawal(4), &[1]).unwrap();
+        assert_eq!(outbox, OutboxMessageId { level: 7, index: 0 });
+        assert_eq!(runtime.outputs(), &[vec![1]]);
+        assert_eq!(ticket.ledger().balance(&mut runtime, &alice), Ok(6));
+
+        let events = runtime.take_events().unwrap();
+        assert_eq!(events.last().unwrap().name, "withdrawn");
+
+        // The message cannot be written, the tickets are not burnt
+        assert!(send_to_outbox(&mut runtime, &alice, 7, &withdrawal(4), &[0; 5000]).is_err());
+        assert_eq!(ticket.ledger().balance(&mut runtime, &alice), Ok(6));
+        assert_eq!(runtime.outputs().len(), 1);
+        assert_eq!(runtime.pending_events(), Ok(0));
+    }
+}
@@ -1,5 +1,7 @@
 use crate::core::{FromInput, Input, Runtime};
 
+pub use rock_n_rollup_macro::FromExternal;
+
 pub trait FromExternal
 where
     Self: Sized,
@@ -65,3 +67,97 @@ impl FromExternal for Vec<u8> {
         Ok(input)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::FromExternal;
+
+    #[derive(FromExternal, PartialEq, Debug)]
+    enum PingPong {
+        Ping,
+        Pong,
+        #[external(tag = 0x0A)]
+        Amount(u64),
+        Transfer {
+            to: String,
+            amount: u64,
+        },
+    }
+
+    #[derive(FromExternal, PartialEq, Debug)]
+    #[external(version = 2)]
+    enum Versioned {
+        Ping,
+        #[external(since = 2)]
+        Pong,
+    }
+
+    #[derive(FromExternal, Serialize, Deserialize, PartialEq, Debug)]
+    #[external(codec = "json", tag = 0x02)]
+    struct Greetings {
+        name: String,
+    }
+
+    #[test]
+    fn test_unit_variant() {
+        assert_eq!(PingPong::from_external(vec![0x00]), Ok(PingPong::Ping));
+        assert_eq!(PingPong::from_external(vec![0x01]), Ok(PingPong::Pong));
+        assert_eq!(PingPong::from_external(vec![0x01, 0x00]), Err(()));
+        assert_eq!(PingPong::from_external(vec![0x04]), Err(()));
+        assert_eq!(PingPong::from_external(vec![]), Err(()));
+    }
+
+    #[test]
+    fn test_explicit_tag() {
+        let mut bytes = vec![0x0A];
+        bytes.append(&mut bincode::serialize(&42u64).unwrap());
+
+        assert_eq!(PingPong::from_external(bytes), Ok(PingPong::Amount(42)));
+    }
+
+    #[test]
+    fn test_named_fields() {
+        let mut bytes = vec![0x03];
+        bytes.append(&mut bincode::serialize(&("alice".to_string(), 10u64)).unwrap());
+
+        assert_eq!(
+            PingPong::from_external(bytes),
+            Ok(PingPong::Transfer {
+                to: "alice".to_string(),
+                amount: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        assert_eq!(
+            Versioned::from_external(vec![0x01, 0x00]),
+            Ok(Versioned::Ping)
+        );
+        assert_eq!(
+            Versioned::from_external(vec![0x02, 0x01]),
+            Ok(Versioned::Pong)
+        );
+        // Pong does not exist in the first version
+        assert_eq!(Versioned::from_external(vec![0x01, 0x01]), Err(()));
+        // The third version is unknown
+        assert_eq!(Versioned::from_external(vec![0x03, 0x00]), Err(()));
+    }
+
+    #[test]
+    fn test_json_struct() {
+        let mut bytes = vec![0x02];
+        bytes.append(&mut "{\"name\":\"alice\"}".as_bytes().to_vec());
+
+        assert_eq!(
+            Greetings::from_external(bytes),
+            Ok(Greetings {
+                name: "alice".to_string()
+            })
+        );
+        assert_eq!(Greetings::from_external(vec![0x01]), Err(()));
+    }
+}
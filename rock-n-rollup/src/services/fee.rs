@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::Runtime,
+    encoding::address::ImplicitAddress,
+    plugins::database::{Bincode, Database},
+};
+
+/// Path of the gas meter of the operation being processed
+const METER_PATH: &str = "/fees/meter";
+
+/// Gas consumed by the transitions of the operation being processed
+///
+/// The metering is opt-in: only the gas consumed with `Fees::consume_gas` is counted.
+/// When the limit is exceeded the operation fails, but the changes already made by its transitions are kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasMeter {
+    /// Units of gas the operation can consume, given by its max fee
+    pub limit: u64,
+    pub used: u64,
+}
+
+impl GasMeter {
+    /// Returns true if the transitions consumed more than the limit
+    pub fn is_exhausted(&self) -> bool {
+        self.used > self.limit
+    }
+}
+
+fn balance_path(account: &ImplicitAddress) -> String {
+    format!("/fees/balances/{}", account)
+}
+
+pub trait Fees {
+    /// Balance available to pay the fees of the operations
+    fn fee_balance(&mut self, account: &ImplicitAddress) -> Result<u64, ()>;
+
+    fn credit_fee(&mut self, account: &ImplicitAddress, amount: u64) -> Result<(), ()>;
+
+    /// Returns an error if the balance is lower than the amount
+    fn debit_fee(&mut self, account: &ImplicitAddress, amount: u64) -> Result<(), ()>;
+
+    /// Consumes some units of gas of the operation being processed
+    ///
+    /// Returns an error when the gas limit of the operation is exceeded, the operation then fails and pays its max fee.
+    /// The changes already written by the transitions are not reverted, so the gas should be consumed before writing.
+    /// The gas is not metered outside of an operation.
+    fn consume_gas(&mut self, units: u64) -> Result<(), ()>;
+}
+
+impl<R> Fees for R
+where
+    R: Runtime,
+{
+    fn fee_balance(&mut self, account: &ImplicitAddress) -> Result<u64, ()> {
+        let balance = <R as Database<Bincode>>::get(self, &balance_path(account))?;
+        Ok(balance.unwrap_or_default())
+    }
+
+    fn credit_fee(&mut self, account: &ImplicitAddress, amount: u64) -> Result<(), ()> {
+        let balance = self.fee_balance(account)?.checked_add(amount).ok_or(())?;
+        <R as Database<Bincode>>::save(self, &balance_path(account), &balance).map(|_| ())
+    }
+
+    fn debit_fee(&mut self, account: &ImplicitAddress, amount: u64) -> Result<(), ()> {
+        let balance = self.fee_balance(account)?.checked_sub(amount).ok_or(())?;
+        <R as Database<Bincode>>::save(self, &balance_path(account), &balance).map(|_| ())
+    }
+
+    fn consume_gas(&mut self, units: u64) -> Result<(), ()> {
+        let meter = <R as Database<Bincode>>::get::<GasMeter>(self, METER_PATH)?;
+        match meter {
+            None => Ok(()),
+            Some(mut meter) => {
+                meter.used = meter.used.saturating_add(units);
+                <R as Database<Bincode>>::save(self, METER_PATH, &meter)?;
+                match meter.is_exhausted() {
+                    true => Err(()),
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Starts metering the gas of an operation
+pub(crate) fn start_metering<R: Runtime>(runtime: &mut R, limit: u64) -> Result<(), ()> {
+    let meter = GasMeter { limit, used: 0 };
+    <R as Database<Bincode>>::save(runtime, METER_PATH, &meter).map(|_| ())
+}
+
+/// Stops metering the gas, returns the meter of the operation if any
+pub(crate) fn stop_metering<R: Runtime>(runtime: &mut R) -> Result<Option<GasMeter>, ()> {
+    let meter = <R as Database<Bincode>>::get(runtime, METER_PATH)?;
+    runtime.store_delete(METER_PATH)?;
+    Ok(meter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{start_metering, stop_metering, Fees, GasMeter};
+    use crate::core::MockRuntime;
+    use crate::services::operation::TestAccount;
+
+    #[test]
+    fn test_balance() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice").address();
+
+        assert_eq!(runtime.fee_balance(&alice), Ok(0));
+        runtime.credit_fee(&alice, 10).unwrap();
+        assert!(runtime.debit_fee(&alice, 11).is_err());
+        runtime.debit_fee(&alice, 4).unwrap();
+        assert_eq!(runtime.fee_balance(&alice), Ok(6));
+        assert!(runtime.credit_fee(&alice, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_gas_meter() {
+        let mut runtime = MockRuntime::default();
+
+        // Not metered
+        assert!(runtime.consume_gas(u64::MAX).is_ok());
+        assert_eq!(stop_metering(&mut runtime), Ok(None));
+
+        start_metering(&mut runtime, 10).unwrap();
+        assert!(runtime.consume_gas(10).is_ok());
+        assert!(runtime.consume_gas(1).is_err());
+        assert_eq!(
+            stop_metering(&mut runtime),
+            Ok(Some(GasMeter {
+                limit: 10,
+                used: 11
+            }))
+        );
+        assert_eq!(stop_metering(&mut runtime), Ok(None));
+    }
+}
@@ -3,7 +3,14 @@ use tezos_smart_rollup_encoding::{
     michelson::Michelson,
 };
 
-use crate::core::{FromInput, Input, Runtime};
+use crate::{
+    core::{FromInput, FromRawInput, Input, RawInput, Runtime},
+    encoding::{
+        address::{ContractAddress, ImplicitAddress, SmartRollupAddress},
+        b58::B58Check,
+    },
+    services::rate_limit::Sender,
+};
 
 pub trait FromInternal
 where
@@ -39,15 +46,15 @@ pub struct StartOfLevel;
 pub struct InfoPerLevel;
 pub struct EndOfLevel;
 
-/// TODO: better types or use tht tezos sdk
+#[derive(Clone)]
 pub struct Transfer<M>
 where
     M: Michelson,
 {
     payload: M,
-    sender: String,
-    source: String,
-    destination: String,
+    sender: ContractAddress,
+    source: ImplicitAddress,
+    destination: SmartRollupAddress,
 }
 
 impl<M> Transfer<M>
@@ -58,15 +65,18 @@ where
         &self.payload
     }
 
-    pub fn sender(&self) -> &String {
+    /// The contract that sent the transfer
+    pub fn sender(&self) -> &ContractAddress {
         &self.sender
     }
 
-    pub fn source(&self) -> &String {
+    /// The account that originated the operation
+    pub fn source(&self) -> &ImplicitAddress {
         &self.source
     }
 
-    pub fn destination(&self) -> &String {
+    /// The rollup receiving the transfer
+    pub fn destination(&self) -> &SmartRollupAddress {
         &self.destination
     }
 }
@@ -108,9 +118,12 @@ where
         match msg {
             InboxMessage::Internal(InternalInboxMessage::Transfer(t)) => {
                 let payload = t.payload;
-                let sender = t.sender.to_base58_check();
-                let source = t.source.to_b58check();
-                let destination = t.destination.to_b58check();
+                let sender =
+                    ContractAddress::from_b58check(&t.sender.to_base58_check()).map_err(|_| ())?;
+                let source =
+                    ImplicitAddress::from_b58check(&t.source.to_b58check()).map_err(|_| ())?;
+                let destination = SmartRollupAddress::from_b58check(&t.destination.to_b58check())
+                    .map_err(|_| ())?;
 
                 Ok(Transfer {
                     payload,
@@ -124,6 +137,26 @@ where
     }
 }
 
+/// A service can process the transfers of a given type
+impl<M> FromRawInput for Transfer<M>
+where
+    M: Michelson,
+{
+    fn from_raw_input<R: Runtime>(_: &mut R, input: &RawInput) -> Result<Self, ()> {
+        Transfer::from_internal(&input.payload)
+    }
+}
+
+/// The contract of the transfer
+impl<M> Sender for Transfer<M>
+where
+    M: Michelson,
+{
+    fn sender(&self) -> String {
+        self.sender.to_string()
+    }
+}
+
 impl<T: FromInternal, S> FromInput<Vec<u8>, S> for Internal<T> {
     fn from_input<R: Runtime>(_: &mut R, input: &Input<Vec<u8>>, _: &S) -> Result<Self, ()> {
         let payload = T::from_internal(&input.payload)?;
@@ -134,3 +167,28 @@ impl<T: FromInternal, S> FromInput<Vec<u8>, S> for Internal<T> {
         })
     }
 }
+
+/// Encodes an internal transfer to the MockRuntime rollup, as it is read from the inbox
+#[cfg(test)]
+pub(crate) fn transfer_message<M: Michelson>(sender: &str, payload: M) -> Vec<u8> {
+    use tezos_smart_rollup_encoding::{
+        contract::Contract, inbox, public_key_hash::PublicKeyHash, smart_rollup,
+    };
+
+    let sender = match Contract::from_b58check(sender).unwrap() {
+        Contract::Originated(sender) => sender,
+        Contract::Implicit(_) => panic!("the sender is not a contract"),
+    };
+    let message = InboxMessage::Internal(InternalInboxMessage::Transfer(inbox::Transfer {
+        payload,
+        sender,
+        source: PublicKeyHash::from_b58check("tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb").unwrap(),
+        destination: smart_rollup::SmartRollupAddress::from_b58check(
+            crate::core::MOCK_ROLLUP_ADDRESS,
+        )
+        .unwrap(),
+    }));
+    let mut bytes = Vec::default();
+    message.serialize(&mut bytes).unwrap();
+    bytes
+}
@@ -0,0 +1,544 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{Input, Runtime},
+    encoding::address::{ImplicitAddress, SmartRollupAddress},
+    plugins::{
+        database::{Backend, Bincode, Database},
+        events::Events,
+        logger::Logger,
+    },
+    services::operation::{RawExternalOperation, Signed, UnsignedOperation},
+};
+
+/// Kind of the operations of the ledgers
+pub const LEDGER_KIND: u8 = 0xFE;
+
+/// Fungible token, its balances are saved under `/ledger/<token>/`
+///
+/// The amounts are checked, a transfer cannot overflow a balance
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ledger {
+    token: String,
+}
+
+/// Action signed by the owner of some tokens
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedgerAction {
+    Transfer {
+        to: ImplicitAddress,
+        amount: u64,
+    },
+    /// Allows the spender to transfer up to `amount` tokens, replaces the previous allowance
+    Approve {
+        spender: ImplicitAddress,
+        amount: u64,
+    },
+    /// Transfers tokens of another account, within the allowance of the signer
+    TransferFrom {
+        from: ImplicitAddress,
+        to: ImplicitAddress,
+        amount: u64,
+    },
+}
+
+/// Payload of the operations of kind LEDGER_KIND, encoded with bincode
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerOperation {
+    pub token: String,
+    pub action: LedgerAction,
+}
+
+impl LedgerOperation {
+    /// Creates the operation to sign on behalf of the account
+    pub fn unsigned_operation(
+        &self,
+        destination: SmartRollupAddress,
+        account: ImplicitAddress,
+        nonce: u64,
+    ) -> Result<UnsignedOperation, ()> {
+        let payload = Bincode::to_bytes(self)?;
+        Ok(UnsignedOperation {
+            kind: LEDGER_KIND,
+            ..UnsignedOperation::new(destination, account, nonce, &payload)
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Minted<'a> {
+    token: &'a str,
+    to: &'a ImplicitAddress,
+    amount: u64,
+}
+
+#[derive(Serialize)]
+struct Burnt<'a> {
+    token: &'a str,
+    from: &'a ImplicitAddress,
+    amount: u64,
+}
+
+#[derive(Serialize)]
+struct Transferred<'a> {
+    token: &'a str,
+    from: &'a ImplicitAddress,
+    to: &'a ImplicitAddress,
+    amount: u64,
+}
+
+#[derive(Serialize)]
+struct Approved<'a> {
+    token: &'a str,
+    owner: &'a ImplicitAddress,
+    spender: &'a ImplicitAddress,
+    amount: u64,
+}
+
+impl Ledger {
+    fn supply_path(&self) -> String {
+        format!("/ledger/{}/supply", self.token)
+    }
+
+    fn balance_path(&self, account: &ImplicitAddress) -> String {
+        format!("/ledger/{}/balances/{}", self.token, account)
+    }
+
+    fn allowance_path(&self, owner: &ImplicitAddress, spender: &ImplicitAddress) -> String {
+        format!("/ledger/{}/allowances/{}/{}", self.token, owner, spender)
+    }
+
+    fn read<R: Runtime>(runtime: &mut R, path: &str) -> Result<u64, ()> {
+        let amount = <R as Database<Bincode>>::get(runtime, path)?;
+        Ok(amount.unwrap_or_default())
+    }
+
+    fn write<R: Runtime>(runtime: &mut R, path: &str, amount: u64) -> Result<(), ()> {
+        <R as Database<Bincode>>::save(runtime, path, &amount).map(|_| ())
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn balance<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        account: &ImplicitAddress,
+    ) -> Result<u64, ()> {
+        Ledger::read(runtime, &self.balance_path(account))
+    }
+
+    pub fn total_supply<R: Runtime>(&self, runtime: &mut R) -> Result<u64, ()> {
+        Ledger::read(runtime, &self.supply_path())
+    }
+
+    /// Amount of tokens of the owner the spender can transfer
+    pub fn allowance<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        owner: &ImplicitAddress,
+        spender: &ImplicitAddress,
+    ) -> Result<u64, ()> {
+        Ledger::read(runtime, &self.allowance_path(owner, spender))
+    }
+
+    /// Creates tokens, for instance when a deposit is received
+    ///
+    /// Returns an error if the total supply overflows
+    pub fn mint<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        to: &ImplicitAddress,
+        amount: u64,
+    ) -> Result<(), ()> {
+        let supply = self.total_supply(runtime)?.checked_add(amount).ok_or(())?;
+        let balance = self.balance(runtime, to)?.checked_add(amount).ok_or(())?;
+        Ledger::write(runtime, &self.supply_path(), supply)?;
+        Ledger::write(runtime, &self.balance_path(to), balance)?;
+        runtime.emit(
+            "minted",
+            &Minted {
+                token: &self.token,
+                to,
+                amount,
+            },
+        )
+    }
+
+    /// Destroys tokens, for instance when a withdrawal is requested
+    ///
+    /// Returns an error if the balance is lower than the amount
+    pub fn burn<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        from: &ImplicitAddress,
+        amount: u64,
+    ) -> Result<(), ()> {
+        let balance = self.balance(runtime, from)?.checked_sub(amount).ok_or(())?;
+        let supply = self.total_supply(runtime)?.checked_sub(amount).ok_or(())?;
+        Ledger::write(runtime, &self.balance_path(from), balance)?;
+        Ledger::write(runtime, &self.supply_path(), supply)?;
+        runtime.emit(
+            "burnt",
+            &Burnt {
+                token: &self.token,
+                from,
+                amount,
+            },
+        )
+    }
+
+    /// Computes the balances of the sender and the recipient after a transfer, without writing them
+    fn transferred_balances<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        from: &ImplicitAddress,
+        to: &ImplicitAddress,
+        amount: u64,
+    ) -> Result<(u64, u64), ()> {
+        let from_balance = self.balance(runtime, from)?.checked_sub(amount).ok_or(())?;
+        // The sender can be the recipient
+        let to_balance = match from == to {
+            true => from_balance,
+            false => self.balance(runtime, to)?,
+        };
+        let to_balance = to_balance.checked_add(amount).ok_or(())?;
+        Ok((from_balance, to_balance))
+    }
+
+    fn write_transfer<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        from: &ImplicitAddress,
+        to: &ImplicitAddress,
+        amount: u64,
+        (from_balance, to_balance): (u64, u64),
+    ) -> Result<(), ()> {
+        Ledger::write(runtime, &self.balance_path(from), from_balance)?;
+        Ledger::write(runtime, &self.balance_path(to), to_balance)?;
+        runtime.emit(
+            "transferred",
+            &Transferred {
+                token: &self.token,
+                from,
+                to,
+                amount,
+            },
+        )
+    }
+
+    /// Returns an error if the balance of the sender is lower than the amount
+    ///
+    /// Nothing is written when the transfer fails
+    pub fn transfer<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        from: &ImplicitAddress,
+        to: &ImplicitAddress,
+        amount: u64,
+    ) -> Result<(), ()> {
+        let balances = self.transferred_balances(runtime, from, to, amount)?;
+        self.write_transfer(runtime, from, to, amount, balances)
+    }
+
+    /// Replaces the allowance of the spender
+    pub fn approve<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        owner: &ImplicitAddress,
+        spender: &ImplicitAddress,
+        amount: u64,
+    ) -> Result<(), ()> {
+        Ledger::write(runtime, &self.allowance_path(owner, spender), amount)?;
+        runtime.emit(
+            "approved",
+            &Approved {
+                token: &self.token,
+                owner,
+                spender,
+                amount,
+            },
+        )
+    }
+
+    /// Transfers tokens of the owner, the allowance of the spender is decreased
+    ///
+    /// Nothing is written when the transfer fails
+    pub fn transfer_from<R: Runtime>(
+        &self,
+        runtime: &mut R,
+        spender: &ImplicitAddress,
+        from: &ImplicitAddress,
+        to: &ImplicitAddress,
+        amount: u64,
+    ) -> Result<(), ()> {
+        let allowance = self
+            .allowance(runtime, from, spender)?
+            .checked_sub(amount)
+            .ok_or(())?;
+        let balances = self.transferred_balances(runtime, from, to, amount)?;
+        Ledger::write(runtime, &self.allowance_path(from, spender), allowance)?;
+        self.write_transfer(runtime, from, to, amount, balances)
+    }
+}
+
+#[rock_n_rollup::service(payload = RawExternalOperation)]
+impl Ledger {
+    /// Creates the service of the given token
+    ///
+    /// Panics if the token is not made of ascii alphanumeric characters, `-` and `_`
+    pub fn new(token: &str) -> Self {
+        let is_valid = !token.is_empty()
+            && token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        assert!(is_valid, "invalid token name: {}", token);
+        Self {
+            token: token.to_string(),
+        }
+    }
+
+    #[guard]
+    fn is_ledger_operation<R: Runtime>(_: &mut R, input: &Input<RawExternalOperation>) -> bool {
+        input.payload.kind() == LEDGER_KIND
+    }
+
+    /// The operations of other tokens are skipped
+    ///
+    /// The operations which cannot be decoded are accepted, so they fail in the transition
+    #[guard]
+    fn is_token<R: Runtime>(
+        _: &mut R,
+        input: &Input<RawExternalOperation>,
+        state: &Ledger,
+    ) -> bool {
+        Bincode::from_bytes(input.payload.payload()).map_or(true, |operation: LedgerOperation| {
+            operation.token == state.token
+        })
+    }
+
+    /// Executes the operations of the token
+    #[transition]
+    fn execute<R: Runtime>(
+        rt: &mut R,
+        operation: Signed<LedgerOperation>,
+        state: Ledger,
+    ) -> Result<(), ()> {
+        let source = operation.source().clone();
+        let LedgerOperation { token, action } = operation.into_payload();
+
+        let result = match &action {
+            LedgerAction::Transfer { to, amount } => state.transfer(rt, &source, to, *amount),
+            LedgerAction::Approve { spender, amount } => {
+                state.approve(rt, &source, spender, *amount)
+            }
+            LedgerAction::TransferFrom { from, to, amount } => {
+                state.transfer_from(rt, &source, from, to, *amount)
+            }
+        };
+        if result.is_err() {
+            rt.warn(&format!(
+                "ledger {}: {:?} of {} failed",
+                token, action, source
+            ));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ledger, LedgerAction, LedgerOperation};
+    use crate::core::{Application, MockRuntime, Runtime};
+    use crate::plugins::events::Events;
+    use crate::services::{
+        operation::{RawExternalOperation, TestAccount},
+        receipt::{Receipts, Status},
+    };
+
+    #[test]
+    fn test_mint_and_burn() {
+        let mut runtime = MockRuntime::default();
+        let ledger = Ledger::new("gold");
+        let alice = TestAccount::new("alice").address();
+
+        ledger.mint(&mut runtime, &alice, 10).unwrap();
+        assert!(ledger.mint(&mut runtime, &alice, u64::MAX).is_err());
+        assert!(ledger.burn(&mut runtime, &alice, 11).is_err());
+        ledger.burn(&mut runtime, &alice, 4).unwrap();
+
+        assert_eq!(ledger.balance(&mut runtime, &alice), Ok(6));
+        assert_eq!(ledger.total_supply(&mut runtime), Ok(6));
+        // Each token has its own balances
+        assert_eq!(Ledger::new("silver").balance(&mut runtime, &alice), Ok(0));
+
+        let events = runtime.take_events().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name, "minted");
+        assert_eq!(
+            events[0].data,
+            format!("{{\"token\":\"gold\",\"to\":\"{}\",\"amount\":10}}", alice)
+        );
+    }
+
+    #[test]
+    fn test_failed_transfer() {
+        let mut runtime = MockRuntime::default();
+        let ledger = Ledger::new("gold");
+        let alice = TestAccount::new("alice").address();
+        let bob = TestAccount::new("bob").address();
+        let carol = TestAccount::new("carol").address();
+
+        ledger.mint(&mut runtime, &alice, 10).unwrap();
+        ledger.approve(&mut runtime, &alice, &carol, 5).unwrap();
+        Ledger::write(&mut runtime, &ledger.balance_path(&bob), u64::MAX).unwrap();
+
+        // The balance of bob would overflow, nothing is written
+        assert!(ledger.transfer(&mut runtime, &alice, &bob, 1).is_err());
+        assert!(ledger
+            .transfer_from(&mut runtime, &carol, &alice, &bob, 1)
+            .is_err());
+        assert_eq!(ledger.balance(&mut runtime, &alice), Ok(10));
+        assert_eq!(ledger.allowance(&mut runtime, &alice, &carol), Ok(5));
+
+        // A transfer to oneself does not change the balance
+        ledger.transfer(&mut runtime, &alice, &alice, 4).unwrap();
+        assert_eq!(ledger.balance(&mut runtime, &alice), Ok(10));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_token() {
+        Ledger::new("gold/silver");
+    }
+
+    fn sign(
+        runtime: &mut MockRuntime,
+        account: &TestAccount,
+        nonce: u64,
+        token: &str,
+        action: LedgerAction,
+    ) -> RawExternalOperation {
+        let operation = LedgerOperation {
+            token: token.to_string(),
+            action,
+        };
+        let content = operation
+            .unsigned_operation(runtime.rollup_address().unwrap(), account.address(), nonce)
+            .unwrap();
+        account.sign(runtime, content)
+    }
+
+    #[test]
+    fn test_signed_operations() {
+        let mut runtime = MockRuntime::default();
+        let ledger = Ledger::new("gold");
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+        let carol = TestAccount::new("carol");
+        let destination = runtime.rollup_address().unwrap();
+        ledger.mint(&mut runtime, &alice.address(), 100).unwrap();
+
+        let operations = [
+            sign(
+                &mut runtime,
+                &alice,
+                1,
+                "gold",
+                LedgerAction::Transfer {
+                    to: bob.address(),
+                    amount: 30,
+                },
+            ),
+            sign(
+                &mut runtime,
+                &alice,
+                2,
+                "gold",
+                LedgerAction::Approve {
+                    spender: carol.address(),
+                    amount: 20,
+                },
+            ),
+            sign(
+                &mut runtime,
+                &carol,
+                1,
+                "gold",
+                LedgerAction::TransferFrom {
+                    from: alice.address(),
+                    to: carol.address(),
+                    amount: 15,
+                },
+            ),
+            // Exceeds the allowance
+            sign(
+                &mut runtime,
+                &carol,
+                2,
+                "gold",
+                LedgerAction::TransferFrom {
+                    from: alice.address(),
+                    to: carol.address(),
+                    amount: 10,
+                },
+            ),
+            // Exceeds the balance
+            sign(
+                &mut runtime,
+                &bob,
+                1,
+                "gold",
+                LedgerAction::Transfer {
+                    to: carol.address(),
+                    amount: 31,
+                },
+            ),
+            // Another token
+            sign(
+                &mut runtime,
+                &bob,
+                2,
+                "silver",
+                LedgerAction::Transfer {
+                    to: carol.address(),
+                    amount: 1,
+                },
+            ),
+            // Not a ledger operation
+            alice.sign_operation(&mut runtime, &destination, 3, &[]),
+        ];
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+        Application::new(&mut runtime).service(ledger.clone()).run();
+
+        assert_eq!(ledger.balance(&mut runtime, &alice.address()), Ok(55));
+        assert_eq!(ledger.balance(&mut runtime, &bob.address()), Ok(30));
+        assert_eq!(ledger.balance(&mut runtime, &carol.address()), Ok(15));
+        assert_eq!(
+            ledger.allowance(&mut runtime, &alice.address(), &carol.address()),
+            Ok(5)
+        );
+        assert_eq!(ledger.total_supply(&mut runtime), Ok(100));
+
+        let statuses = operations
+            .iter()
+            .map(|operation| {
+                let hash = operation.hash(&mut runtime);
+                runtime.receipt(&hash).unwrap().unwrap().status
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![
+                Status::Applied,
+                Status::Applied,
+                Status::Applied,
+                Status::Failed,
+                Status::Failed,
+                Status::Skipped,
+                Status::Skipped,
+            ]
+        );
+    }
+}
@@ -1,7 +1,19 @@
+pub mod account;
+
+pub mod bridge;
+
 pub mod external;
 
+pub mod fee;
+
 pub mod internal;
 
+pub mod ledger;
+
 pub mod operation;
 
+pub mod rate_limit;
+
+pub mod receipt;
+
 pub mod ticket_upgrade;
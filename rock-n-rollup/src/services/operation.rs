@@ -1,129 +1,1292 @@
-use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+use blake2::digest::{Update, VariableOutput};
+use blake2::Blake2bVar;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    core::{FromInput, FromRawInput},
+    core::{FromInput, FromRawInput, Input, Outcome, RawInput, Runtime},
+    encoding::{
+        address::{ImplicitAddress, SmartRollupAddress},
+        codec::Codec,
+    },
     plugins::{
-        crypto::{PublicKey, Signature, Verifier},
-        database::{Bincode, Database},
-        hasher::Hasher,
+        crypto::{Curve, PublicKey, SecretKey, Signature, Signer, Verifier},
+        database::{Backend, Bincode, Database},
+        events::{Event, Events},
+        hasher::{Blake2b512, Hasher},
+        logger::Logger,
+    },
+    services::{
+        account::{Accounts, KeyUpdate, KEYS_KIND},
+        fee::{start_metering, stop_metering, Fees},
+        receipt::{OperationHash, Receipt, Receipts, Rejection, Status},
     },
 };
 
 /// The idea is to have a common operation that represent external message
 ///
-/// Here is the encoding of this operation:
+/// Here is the binary encoding of this operation:
+/// ------------
+/// 0x01                      external message
+/// 0x00                      operation tag
+/// 0x04                      version of the encoding
+/// rollup-address            20 bytes
+/// account                   1 byte for the curve + 20 bytes for the hash of the address
+/// kind                      1 byte
+/// curve                     1 byte
+/// public_key                u32 length + bytes
+/// nonce                     u64
+/// valid_until_level         u32
+/// max_fee                   u64
+/// signature                 u32 length + bytes
+/// payload                   u32 length + bytes
+///------------
+///
+/// A sponsor can wrap an operation signed by a user, to pay for it:
 /// ------------
-/// 0x01\n
-/// rollup-address\n
-/// public_key\n
-/// nonce\n
-/// signature\n
-/// payload
+/// 0x01                      external message
+/// 0x02                      sponsored tag
+/// 0x04                      version of the encoding
+/// operation                 u32 length + operation of the user encoded from its tag
+/// account                   curve (1 byte) + hash (20 bytes), of the sponsor
+/// curve                     1 byte
+/// public_key                u32 length + bytes, one of the keys of the sponsor
+/// nonce                     u64, of the sponsor
+/// signature                 u32 length + bytes, of the sponsor
 ///------------
 ///
-/// The signature represent the signature of `{nonce}{rollup-address}{hash of the payload}`
+/// Several operations can be sent in one external message with a batch:
+/// ------------
+/// 0x01                      external message
+/// 0x01                      batch tag
+/// 0x04                      version of the encoding
+/// operations                u32 length + operation encoded from its tag, for each operation
+///------------
+///
+/// Numbers are encoded in big endian,
+/// the public key and the signature are encoded with their raw bytes (compressed for ecdsa keys).
+///
+/// The signature represent the signature of `{nonce}{valid_until_level}{max_fee}{rollup-address}{account}{kind}{hash of the payload}`
+///
+/// The public key has to be one of the keys of the account, see the account module.
+/// The kind of the operation is defined by the application, it restricts what a session key can sign.
+///
+/// The signature of the sponsor represent the signature of `{nonce}{account}{hash of the operation of the user}`,
+/// its public key has to be authorized by the account of the sponsor for the kind of the operation.
+/// The nonces of the user and of the sponsor are both consumed, so the operation of the user cannot be sponsored twice.
+///
+/// The operation is rejected after the level `valid_until_level`.
+/// By default the nonce of an operation has to be the previous nonce of the account plus one,
+/// see OperationConfig to accept operations arriving out of order.
+///
+/// The max fee is debited from the balance of the fee payer before the operation is processed, see the fee module.
+/// The fee paid depends on the gas consumed by the transitions, the rest of the max fee is refunded.
+///
+/// Each decoded operation gets a receipt under `/receipts/<operation hash>`, see the receipt module.
 ///
 /// A typescript library should be provided to ease the development of rollup
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct RawExternalOperation {
-    destination: String,
+    content: UnsignedOperation,
+    public_key: PublicKey,
+    signature: Signature,
+    sponsor: Option<Sponsor>,
+}
+
+/// Content of an operation, signed by one of the keys of the account
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsignedOperation {
+    pub destination: SmartRollupAddress,
+    /// The account on behalf of which the operation is signed
+    pub account: ImplicitAddress,
+    pub kind: u8,
+    pub nonce: u64,
+    pub valid_until_level: u32,
+    /// Maximum fee paid for the operation, it also gives the gas limit of the operation
+    pub max_fee: u64,
+    pub payload: Vec<u8>,
+}
+
+impl UnsignedOperation {
+    /// Creates an operation of kind 0, it never expires and has no fee
+    pub fn new(
+        destination: SmartRollupAddress,
+        account: ImplicitAddress,
+        nonce: u64,
+        payload: &[u8],
+    ) -> Self {
+        UnsignedOperation {
+            destination,
+            account,
+            kind: 0,
+            nonce,
+            valid_until_level: u32::MAX,
+            max_fee: 0,
+            payload: payload.to_vec(),
+        }
+    }
+
+    /// Creates an operation updating the keys of the account
+    pub fn key_update(
+        destination: SmartRollupAddress,
+        account: ImplicitAddress,
+        nonce: u64,
+        update: &KeyUpdate,
+    ) -> Result<Self, ()> {
+        let payload = Bincode::to_bytes(update)?;
+        Ok(UnsignedOperation {
+            kind: KEYS_KIND,
+            ..UnsignedOperation::new(destination, account, nonce, &payload)
+        })
+    }
+}
+
+/// Sponsor of an operation, signing over the operation of the user
+#[derive(Clone, Debug, PartialEq)]
+struct Sponsor {
+    /// The account paying for the operation
+    account: ImplicitAddress,
     public_key: PublicKey,
     nonce: u64,
     signature: Signature,
-    payload: Vec<u8>,
+}
+
+/// Tag of the external messages in the inbox
+const EXTERNAL_TAG: u8 = 0x01;
+
+/// Tag of a single operation
+const OPERATION_TAG: u8 = 0x00;
+
+/// Tag of a batch of operations
+const BATCH_TAG: u8 = 0x01;
+
+/// Tag of a sponsored operation
+const SPONSORED_TAG: u8 = 0x02;
+
+/// Current version of the encoding
+const OPERATION_VERSION: u8 = 0x04;
+
+/// Path of the configuration of the operations
+const CONFIG_PATH: &str = "/operation/config";
+
+/// Number of events pending before the operation being processed, they are not part of its receipt
+const EVENTS_START_PATH: &str = "/operation/events-start";
+
+/// Path of the operations of the input being processed by the services
+///
+/// Each operation is saved under its own path, as the storage is written by chunks of at most 2048 bytes
+const CURRENT_INPUT_PATH: &str = "/operation/current-input";
+
+/// Input whose operations are being processed
+///
+/// The operations are verified by the first service processing the input, the next services reuse them.
+/// The fees and the receipts are written once every service processed the input.
+/// The operations are decoded again from the input, only what the services did with them is saved.
+#[derive(PartialEq, Eq, Serialize, Deserialize)]
+struct CurrentInput {
+    level: u32,
+    id: u32,
+}
+
+/// Verified operation, and what the services did with it so far
+#[derive(Serialize, Deserialize)]
+struct ProcessedOperation {
+    status: Status,
+    gas_used: u64,
+    /// Number of events emitted by the transitions, saved under `events_path`
+    events: usize,
+}
+
+fn input_path() -> String {
+    format!("{}/input", CURRENT_INPUT_PATH)
+}
+
+fn processed_path(index: usize) -> String {
+    format!("{}/operations/{}", CURRENT_INPUT_PATH, index)
+}
+
+fn events_path(index: usize, event: usize) -> String {
+    format!("{}/events/{}/{}", CURRENT_INPUT_PATH, index, event)
+}
+
+/// Path of the index of a verified operation in its input
+///
+/// An operation cannot be verified twice, so its hash identifies it in the input
+fn index_path(hash: &OperationHash) -> String {
+    format!("{}/indexes/{}", CURRENT_INPUT_PATH, hash)
+}
+
+impl CurrentInput {
+    /// Returns true if the operations of the input have already been verified
+    fn is_verified<R: Runtime>(runtime: &mut R, level: u32, id: u32) -> Result<bool, ()> {
+        let current = <R as Database<Bincode>>::get::<CurrentInput>(runtime, &input_path())?;
+        Ok(current == Some(CurrentInput { level, id }))
+    }
+
+    /// Saves the verified operations of the input, the other ones are not saved
+    fn save<R: Runtime>(
+        runtime: &mut R,
+        level: u32,
+        id: u32,
+        operations: &[Result<RawExternalOperation, ()>],
+    ) -> Result<(), ()> {
+        runtime.store_delete(CURRENT_INPUT_PATH)?;
+        <R as Database<Bincode>>::save(runtime, &input_path(), &CurrentInput { level, id })?;
+        for (index, operation) in operations.iter().enumerate() {
+            if let Ok(operation) = operation {
+                let hash = operation.hash(runtime);
+                <R as Database<Bincode>>::save(runtime, &index_path(&hash), &index)?;
+                let processed = ProcessedOperation {
+                    status: Status::Skipped,
+                    gas_used: 0,
+                    events: 0,
+                };
+                <R as Database<Bincode>>::save(runtime, &processed_path(index), &processed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the verified operation in the input being processed
+    fn index<R: Runtime>(
+        runtime: &mut R,
+        operation: &RawExternalOperation,
+    ) -> Result<Option<usize>, ()> {
+        let hash = operation.hash(runtime);
+        <R as Database<Bincode>>::get(runtime, &index_path(&hash))
+    }
+
+    fn load_processed<R: Runtime>(
+        runtime: &mut R,
+        index: usize,
+    ) -> Result<Option<ProcessedOperation>, ()> {
+        <R as Database<Bincode>>::get(runtime, &processed_path(index))
+    }
+
+    /// Merges the status, the gas and the events given by a service into the operation
+    fn record<R: Runtime>(
+        runtime: &mut R,
+        operation: &RawExternalOperation,
+        status: Status,
+        gas_used: u64,
+        events: Vec<Event>,
+    ) -> Result<(), ()> {
+        let index = Self::index(runtime, operation)?.ok_or(())?;
+        let mut processed = Self::load_processed(runtime, index)?.ok_or(())?;
+        for event in events {
+            <R as Database<Bincode>>::save(runtime, &events_path(index, processed.events), &event)?;
+            processed.events += 1;
+        }
+        let previous = std::mem::replace(&mut processed.status, Status::Skipped);
+        processed.status = merge_status(previous, status);
+        processed.gas_used = processed.gas_used.saturating_add(gas_used);
+        <R as Database<Bincode>>::save(runtime, &processed_path(index), &processed).map(|_| ())
+    }
+
+    /// Reads the events emitted by the transitions for the operation
+    fn events<R: Runtime>(
+        runtime: &mut R,
+        index: usize,
+        processed: &ProcessedOperation,
+    ) -> Result<Vec<Event>, ()> {
+        (0..processed.events)
+            .map(|event| {
+                <R as Database<Bincode>>::get(runtime, &events_path(index, event))?.ok_or(())
+            })
+            .collect()
+    }
+}
+
+/// Decodes the operations of an input, a single operation or a batch
+fn decode_input(payload: &[u8]) -> Result<Vec<Result<RawExternalOperation, ()>>, ()> {
+    match try_from_batch(payload) {
+        Ok(operations) => Ok(operations),
+        Err(_) => try_from_bytes(payload).map(|operation| vec![Ok(operation)]),
+    }
+}
+
+/// Status of an operation processed by several services
+///
+/// The operation fails if a service failed, otherwise it is applied if a service applied it
+fn merge_status(left: Status, right: Status) -> Status {
+    match (left, right) {
+        (Status::Failed, _) | (_, Status::Failed) => Status::Failed,
+        (Status::Applied, _) | (_, Status::Applied) => Status::Applied,
+        _ => Status::Skipped,
+    }
+}
+
+/// Maximum size of the nonce window
+pub const MAX_NONCE_WINDOW: u8 = 64;
+
+impl RawExternalOperation {
+    /// The rollup targeted by the operation
+    pub fn destination(&self) -> &SmartRollupAddress {
+        &self.content.destination
+    }
+
+    /// The public key of the signer of the operation
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// The account on behalf of which the operation is signed
+    ///
+    /// It is the address of the signer, unless the signer is another key of the account
+    pub fn source(&self) -> ImplicitAddress {
+        self.content.account.clone()
+    }
+
+    pub fn kind(&self) -> u8 {
+        self.content.kind
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.content.nonce
+    }
+
+    /// The last level at which the operation can be processed
+    pub fn valid_until_level(&self) -> u32 {
+        self.content.valid_until_level
+    }
+
+    /// The maximum fee paid by the fee payer
+    pub fn max_fee(&self) -> u64 {
+        self.content.max_fee
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.content.payload
+    }
+
+    /// The address of the sponsor, if the operation is sponsored
+    pub fn sponsor(&self) -> Option<ImplicitAddress> {
+        self.sponsor.as_ref().map(|sponsor| sponsor.account.clone())
+    }
+
+    /// The address paying for the operation: the sponsor if any, otherwise the account
+    pub fn fee_payer(&self) -> ImplicitAddress {
+        self.sponsor().unwrap_or_else(|| self.source())
+    }
+
+    /// Encodes the operation to the bytes expected in the inbox
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![EXTERNAL_TAG];
+        self.encode(&mut bytes);
+        bytes
+    }
+
+    /// Hash of the operation, identifying its receipt
+    ///
+    /// The hash of an operation does not depend on the batch containing it
+    pub fn hash<R: Hasher>(&self, runtime: &mut R) -> OperationHash {
+        let hash = runtime.hash(&self.to_bytes());
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(hash.as_ref());
+        OperationHash::from(bytes)
+    }
+
+    /// Encodes several operations in one external message
+    pub fn batch_to_bytes(operations: &[RawExternalOperation]) -> Vec<u8> {
+        let mut bytes = vec![EXTERNAL_TAG, BATCH_TAG, OPERATION_VERSION];
+        for operation in operations {
+            let mut encoded = Vec::default();
+            operation.encode(&mut encoded);
+            write_bytes(&mut bytes, &encoded);
+        }
+        bytes
+    }
+
+    /// Encodes the operation from its tag
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match &self.sponsor {
+            None => self.encode_unsponsored(bytes),
+            Some(sponsor) => {
+                bytes.extend([SPONSORED_TAG, OPERATION_VERSION]);
+                let mut operation = Vec::default();
+                self.encode_unsponsored(&mut operation);
+                write_bytes(bytes, &operation);
+                write_account(bytes, &sponsor.account);
+                bytes.push(sponsor.public_key.curve().tag());
+                write_bytes(bytes, &sponsor.public_key.to_bytes());
+                bytes.extend(sponsor.nonce.to_be_bytes());
+                write_bytes(bytes, &sponsor.signature.to_bytes());
+            }
+        }
+    }
+
+    /// Encodes the operation signed by the user, without its sponsor
+    fn encode_unsponsored(&self, bytes: &mut Vec<u8>) {
+        let content = &self.content;
+        bytes.extend([OPERATION_TAG, OPERATION_VERSION]);
+        bytes.extend(content.destination.as_bytes());
+        write_account(bytes, &content.account);
+        bytes.push(content.kind);
+        bytes.push(self.public_key.curve().tag());
+        write_bytes(bytes, &self.public_key.to_bytes());
+        bytes.extend(content.nonce.to_be_bytes());
+        bytes.extend(content.valid_until_level.to_be_bytes());
+        bytes.extend(content.max_fee.to_be_bytes());
+        write_bytes(bytes, &self.signature.to_bytes());
+        write_bytes(bytes, &content.payload);
+    }
+}
+
+/// Writes the bytes prefixed by their length
+fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    // The size of an inbox message is way lower than u32::MAX
+    let size = bytes.len() as u32;
+    buffer.extend(size.to_be_bytes());
+    buffer.extend(bytes);
+}
+
+/// Writes the curve of the address followed by its hash
+fn write_account(buffer: &mut Vec<u8>, account: &ImplicitAddress) {
+    buffer.push(account.curve().tag());
+    buffer.extend(account.as_bytes());
+}
+
+/// Reads the fields of an encoded operation
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], ()> {
+        if size > self.bytes.len() {
+            return Err(());
+        }
+        let (taken, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ()> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ()> {
+        let bytes = self.take(4)?.try_into().map_err(|_| ())?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, ()> {
+        let bytes = self.take(8)?.try_into().map_err(|_| ())?;
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    /// Reads bytes prefixed by their length
+    fn bytes(&mut self) -> Result<&'a [u8], ()> {
+        let size = self.u32()?;
+        self.take(usize::try_from(size).map_err(|_| ())?)
+    }
+
+    fn account(&mut self) -> Result<ImplicitAddress, ()> {
+        let curve = Curve::from_tag(self.u8()?)?;
+        let hash = self.take(20)?.try_into().map_err(|_| ())?;
+        Ok(ImplicitAddress::from_hash(curve, hash))
+    }
+}
+
+/// Computes the hash signed by the author of the operation
+fn signed_hash<R: Hasher>(runtime: &mut R, content: &UnsignedOperation) -> Blake2b512 {
+    let mut payload_hash = runtime.hash_512(&content.payload).as_ref().to_vec();
+    let mut to_hash = Vec::default();
+    to_hash.extend(content.nonce.to_be_bytes());
+    to_hash.extend(content.valid_until_level.to_be_bytes());
+    to_hash.extend(content.max_fee.to_be_bytes());
+    to_hash.extend(content.destination.as_bytes());
+    write_account(&mut to_hash, &content.account);
+    to_hash.push(content.kind);
+    to_hash.append(&mut payload_hash);
+
+    runtime.hash_512(&to_hash)
+}
+
+/// Computes the hash signed by the sponsor of the operation
+fn sponsor_signed_hash<R: Hasher>(
+    runtime: &mut R,
+    account: &ImplicitAddress,
+    nonce: u64,
+    operation: &RawExternalOperation,
+) -> Blake2b512 {
+    let mut encoded = Vec::default();
+    operation.encode_unsponsored(&mut encoded);
+    let mut operation_hash = runtime.hash_512(&encoded).as_ref().to_vec();
+    let mut to_hash = nonce.to_be_bytes().to_vec();
+    write_account(&mut to_hash, account);
+    to_hash.append(&mut operation_hash);
+
+    runtime.hash_512(&to_hash)
+}
+
+/// Decodes an operation from its tag, every byte should be consumed
+fn decode(bytes: &[u8]) -> Result<RawExternalOperation, ()> {
+    match bytes {
+        [SPONSORED_TAG, ..] => decode_sponsored(bytes),
+        _ => decode_unsponsored(bytes),
+    }
+}
+
+/// Decodes a sponsored operation, the operation of the user cannot be sponsored twice
+fn decode_sponsored(bytes: &[u8]) -> Result<RawExternalOperation, ()> {
+    let mut reader = Reader { bytes };
+
+    let header = reader.take(2)?;
+    if header != [SPONSORED_TAG, OPERATION_VERSION] {
+        return Err(());
+    }
+
+    let mut operation = decode_unsponsored(reader.bytes()?)?;
+
+    let account = reader.account()?;
+    let curve = Curve::from_tag(reader.u8()?)?;
+    let public_key = PublicKey::from_bytes(curve, reader.bytes()?)?;
+    let nonce = reader.u64()?;
+    let signature = Signature::from_bytes(curve, reader.bytes()?)?;
+
+    if !reader.bytes.is_empty() {
+        return Err(());
+    }
+
+    operation.sponsor = Some(Sponsor {
+        account,
+        public_key,
+        nonce,
+        signature,
+    });
+    Ok(operation)
+}
+
+/// Decodes an operation signed by a user
+fn decode_unsponsored(bytes: &[u8]) -> Result<RawExternalOperation, ()> {
+    let mut reader = Reader { bytes };
+
+    let header = reader.take(2)?;
+    if header != [OPERATION_TAG, OPERATION_VERSION] {
+        return Err(());
+    }
+
+    let destination: [u8; 20] = reader.take(20)?.try_into().map_err(|_| ())?;
+    let destination = SmartRollupAddress::from(destination);
+
+    let account = reader.account()?;
+    let kind = reader.u8()?;
+
+    let curve = Curve::from_tag(reader.u8()?)?;
+    let public_key = PublicKey::from_bytes(curve, reader.bytes()?)?;
+
+    let nonce = reader.u64()?;
+    let valid_until_level = reader.u32()?;
+    let max_fee = reader.u64()?;
+
+    // The signature is made with the curve of the public key
+    let signature = Signature::from_bytes(curve, reader.bytes()?)?;
+
+    let payload = reader.bytes()?.to_vec();
+
+    // Every byte of the message should be consumed
+    if !reader.bytes.is_empty() {
+        return Err(());
+    }
+
+    Ok(RawExternalOperation {
+        content: UnsignedOperation {
+            destination,
+            account,
+            kind,
+            nonce,
+            valid_until_level,
+            max_fee,
+            payload,
+        },
+        public_key,
+        signature,
+        sponsor: None,
+    })
 }
 
 /// Convert some bytes to an external operation
 ///
 /// Should be private because it does not verify the signature
 fn try_from_bytes(value: &[u8]) -> Result<RawExternalOperation, ()> {
-    if !value.starts_with(&[1]) {
+    match value {
+        [EXTERNAL_TAG, operation @ ..] => decode(operation),
+        _ => Err(()),
+    }
+}
+
+/// Convert a batch to its external operations
+///
+/// Each operation is decoded on its own, a malformed operation does not prevent the others from being decoded
+fn try_from_batch(value: &[u8]) -> Result<Vec<Result<RawExternalOperation, ()>>, ()> {
+    let mut reader = Reader { bytes: value };
+
+    let header = reader.take(3)?;
+    if header != [EXTERNAL_TAG, BATCH_TAG, OPERATION_VERSION] {
         return Err(());
     }
-    let value = value.iter().skip(1).copied().collect::<Vec<u8>>();
 
-    let mut iter = value.split(|byte| byte == &0xA);
-    let destination = iter.next().map(|bytes| bytes.to_vec());
-    let public_key = iter.next().map(|bytes| bytes.to_vec());
-    let nonce = iter.next().map(|bytes| bytes.to_vec());
-    let signature = iter.next().map(|bytes| bytes.to_vec());
-    let payload = iter
-        .map(|x| x.to_vec())
-        .collect::<Vec<Vec<u8>>>()
-        .join(&0xA);
+    let mut operations = Vec::default();
+    while !reader.bytes.is_empty() {
+        let operation = reader.bytes()?;
+        operations.push(decode(operation));
+    }
+    Ok(operations)
+}
+
+/// Configuration of the operations, saved in the durable storage
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationConfig {
+    /// Number of nonces accepted in any order
+    ///
+    /// With a window of 0, the nonce has to be the previous one plus one.
+    /// With a window of N, any unused nonce greater than `highest nonce - N` and lower or equal to `highest nonce + N` is accepted
+    pub nonce_window: u8,
+    /// Number of levels the receipts are kept, they are kept forever with 0
+    pub receipt_retention: u32,
+    /// Fee paid by every operation, an operation with a lower max fee is rejected
+    pub base_fee: u64,
+    /// Fee paid for each unit of gas consumed by the transitions
+    pub gas_price: u64,
+    /// Account receiving the fees, they are burnt without collector
+    pub fee_collector: Option<ImplicitAddress>,
+}
+
+impl OperationConfig {
+    pub fn load<R: Runtime>(runtime: &mut R) -> Result<OperationConfig, ()> {
+        let config = <R as Database<Bincode>>::get(runtime, CONFIG_PATH)?;
+        Ok(config.unwrap_or_default())
+    }
+
+    pub fn save<R: Runtime>(&self, runtime: &mut R) -> Result<(), ()> {
+        if self.nonce_window > MAX_NONCE_WINDOW {
+            return Err(());
+        }
+        <R as Database<Bincode>>::save(runtime, CONFIG_PATH, self).map(|_| ())
+    }
+
+    /// Units of gas an operation can consume with the given max fee
+    pub fn gas_limit(&self, max_fee: u64) -> u64 {
+        match self.gas_price {
+            0 => u64::MAX,
+            gas_price => max_fee.saturating_sub(self.base_fee) / gas_price,
+        }
+    }
+
+    /// Fee of an operation which consumed some gas, it never exceeds the max fee
+    pub fn fee(&self, gas_used: u64, max_fee: u64) -> u64 {
+        self.base_fee
+            .saturating_add(gas_used.saturating_mul(self.gas_price))
+            .min(max_fee)
+    }
+}
+
+fn nonce_path(account: &ImplicitAddress) -> String {
+    format!("/accounts/{}/nonce", account)
+}
+
+fn bitmap_path(account: &ImplicitAddress) -> String {
+    format!("/accounts/{}/nonce-bitmap", account)
+}
+
+/// Nonces used by an account
+///
+/// The bit i of the bitmap is set when the nonce `highest - i` has been used
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Nonces {
+    highest: u64,
+    bitmap: u64,
+}
+
+impl Nonces {
+    fn load<R: Runtime>(runtime: &mut R, account: &ImplicitAddress) -> Result<Nonces, ()> {
+        let highest = <R as Database<Bincode>>::get(runtime, &nonce_path(account))?;
+        let bitmap = <R as Database<Bincode>>::get(runtime, &bitmap_path(account))?;
+        Ok(Nonces {
+            highest: highest.unwrap_or_default(),
+            // Without a bitmap, all the nonces up to the highest one are considered as used
+            bitmap: bitmap.unwrap_or(u64::MAX),
+        })
+    }
+
+    /// Saves the bitmap even without a nonce window, so the used nonces are known if a window is set later
+    fn save<R: Runtime>(&self, runtime: &mut R, account: &ImplicitAddress) -> Result<(), ()> {
+        <R as Database<Bincode>>::save(runtime, &nonce_path(account), &self.highest)?;
+        <R as Database<Bincode>>::save(runtime, &bitmap_path(account), &self.bitmap)?;
+        Ok(())
+    }
+
+    /// Marks the nonce as used, returns an error if it is not accepted by the window
+    fn consume(&mut self, nonce: u64, window: u8) -> Result<(), ()> {
+        let window = u64::from(window);
+        if window == 0 {
+            return match Some(nonce) == self.highest.checked_add(1) {
+                true => {
+                    self.highest = nonce;
+                    // Without a window, all the nonces up to the highest one are used
+                    self.bitmap = u64::MAX;
+                    Ok(())
+                }
+                false => Err(()),
+            };
+        }
 
-    match (destination, public_key, nonce, signature, payload) {
-        (Some(destination), Some(public_key), Some(nonce), Some(signature), payload) => {
-            let destination = String::from_utf8(destination).map_err(|_| ())?;
+        // The nonces start at 1
+        if nonce == 0 {
+            return Err(());
+        }
+
+        if nonce > self.highest {
+            let shift = nonce - self.highest;
+            if shift > window {
+                return Err(());
+            }
+            self.bitmap = self.bitmap.checked_shl(shift as u32).unwrap_or_default() | 1;
+            self.highest = nonce;
+            Ok(())
+        } else {
+            let index = self.highest - nonce;
+            if index >= window {
+                return Err(());
+            }
+            let bit = 1 << index;
+            if self.bitmap & bit != 0 {
+                return Err(());
+            }
+            self.bitmap |= bit;
+            Ok(())
+        }
+    }
+}
 
-            let public_key = String::from_utf8(public_key).map_err(|_| ())?;
-            let public_key = PublicKey::try_from(public_key)?;
+/// Verifies the destination, the key, the signature, the expiry, the fee, the nonce and the balance of the fee payer
+///
+/// The nonce of the account is saved and the max fee is debited when the operation is valid
+fn verify<R: Runtime + Database<Bincode> + Hasher + Verifier>(
+    runtime: &mut R,
+    level: u32,
+    config: &OperationConfig,
+    unverified: RawExternalOperation,
+) -> Result<RawExternalOperation, Rejection> {
+    // The operation may target another rollup
+    let rollup_address = runtime
+        .rollup_address()
+        .map_err(|_| Rejection::StorageError)?;
+    if unverified.content.destination != rollup_address {
+        return Err(Rejection::WrongDestination);
+    }
 
-            let nonce = u64::from_be_bytes(nonce.try_into().map_err(|_| ())?);
+    // The signature is verified first, the other rejections are only given to the operations of the account
 
-            let signature = String::from_utf8(signature).map_err(|_| ())?;
-            let signature = Signature::try_from(signature)?;
+    let source = unverified.source();
+    let is_authorized = runtime
+        .is_authorized(
+            &source,
+            &unverified.public_key,
+            unverified.content.kind,
+            level,
+        )
+        .map_err(|_| Rejection::StorageError)?;
+    if !is_authorized {
+        return Err(Rejection::UnauthorizedKey);
+    }
 
-            Ok(RawExternalOperation {
-                destination,
-                public_key,
+    let hash = signed_hash(runtime, &unverified.content);
+    let signature = &unverified.signature;
+    let public_key = &unverified.public_key;
+    if !runtime.verify_signature(signature, public_key, hash.as_ref()) {
+        return Err(Rejection::InvalidSignature);
+    }
+
+    if level > unverified.content.valid_until_level {
+        return Err(Rejection::Expired);
+    }
+
+    if unverified.content.max_fee < config.base_fee {
+        return Err(Rejection::FeeTooLow);
+    }
+
+    let mut nonces = Nonces::load(runtime, &source).map_err(|_| Rejection::StorageError)?;
+
+    nonces
+        .consume(unverified.content.nonce, config.nonce_window)
+        .map_err(|_| Rejection::InvalidNonce)?;
+
+    // The nonces of the sponsor, if it is another account
+    let mut sponsor_nonces = None;
+    if let Some(sponsor) = &unverified.sponsor {
+        // The key of the sponsor is looked up like the key of the account, it may have been rotated
+        let sponsor_address = sponsor.account.clone();
+        let is_authorized = runtime
+            .is_authorized(
+                &sponsor_address,
+                &sponsor.public_key,
+                unverified.content.kind,
+                level,
+            )
+            .map_err(|_| Rejection::StorageError)?;
+        if !is_authorized {
+            return Err(Rejection::UnauthorizedSponsorKey);
+        }
+
+        // A user can sponsor its own operation, then both nonces are taken from the same account
+        let consumed = match sponsor_address == source {
+            true => nonces.consume(sponsor.nonce, config.nonce_window),
+            false => {
+                let mut loaded =
+                    Nonces::load(runtime, &sponsor_address).map_err(|_| Rejection::StorageError)?;
+                let consumed = loaded.consume(sponsor.nonce, config.nonce_window);
+                sponsor_nonces = Some((sponsor_address, loaded));
+                consumed
+            }
+        };
+        consumed.map_err(|_| Rejection::InvalidSponsorNonce)?;
+
+        let hash = sponsor_signed_hash(runtime, &sponsor.account, sponsor.nonce, &unverified);
+        if !runtime.verify_signature(&sponsor.signature, &sponsor.public_key, hash.as_ref()) {
+            return Err(Rejection::InvalidSponsorSignature);
+        }
+    }
+
+    // The max fee is held until the operation is processed
+    let max_fee = unverified.content.max_fee;
+    let fee_payer = unverified.fee_payer();
+    if max_fee > 0 {
+        let balance = runtime
+            .fee_balance(&fee_payer)
+            .map_err(|_| Rejection::StorageError)?;
+        if balance < max_fee {
+            return Err(Rejection::InsufficientBalance);
+        }
+    }
+
+    // Every check passed, the nonces are saved and the max fee is debited
+    nonces
+        .save(runtime, &source)
+        .map_err(|_| Rejection::StorageError)?;
+    if let Some((sponsor_address, sponsor_nonces)) = sponsor_nonces {
+        sponsor_nonces
+            .save(runtime, &sponsor_address)
+            .map_err(|_| Rejection::StorageError)?;
+    }
+    if max_fee > 0 {
+        runtime
+            .debit_fee(&fee_payer, max_fee)
+            .map_err(|_| Rejection::StorageError)?;
+    }
+
+    // And then the operation is a verified one
+    Ok(unverified)
+}
+
+/// Pays the fee of a processed operation to the collector, and refunds the rest of its max fee
+fn settle_fee<R: Runtime>(
+    runtime: &mut R,
+    config: &OperationConfig,
+    operation: &RawExternalOperation,
+    gas_used: u64,
+) -> Result<u64, ()> {
+    let max_fee = operation.max_fee();
+    let fee = config.fee(gas_used, max_fee);
+    if max_fee > fee {
+        runtime.credit_fee(&operation.fee_payer(), max_fee - fee)?;
+    }
+    if let Some(collector) = &config.fee_collector {
+        if fee > 0 {
+            runtime.credit_fee(collector, fee)?;
+        }
+    }
+    Ok(fee)
+}
+
+/// Returns true if the operation may not have been signed by its account, or is for another rollup
+///
+/// Anyone can send such operations for free, so they do not get a receipt
+fn is_unsigned(rejection: &Rejection) -> bool {
+    matches!(
+        rejection,
+        Rejection::WrongDestination
+            | Rejection::UnauthorizedKey
+            | Rejection::InvalidSignature
+            | Rejection::UnauthorizedSponsorKey
+            | Rejection::InvalidSponsorSignature
+    )
+}
+
+/// Verifies the operation and writes the receipt of the operations not processed by the service
+///
+/// The rejected operations and the updates of keys are not given to the transitions
+fn verify_or_reject<R: Runtime + Database<Bincode> + Hasher + Verifier>(
+    runtime: &mut R,
+    level: u32,
+    config: &OperationConfig,
+    unverified: RawExternalOperation,
+) -> Result<RawExternalOperation, ()> {
+    let hash = unverified.hash(runtime);
+    let (status, fee) = match verify(runtime, level, config, unverified) {
+        Ok(operation) if operation.kind() == KEYS_KIND => {
+            let applied =
+                Bincode::from_bytes::<KeyUpdate>(operation.payload()).and_then(|update| {
+                    runtime.update_keys(&operation.source(), &operation.public_key, &update, level)
+                });
+            let status = match applied {
+                Ok(()) => Status::Applied,
+                Err(()) => Status::Failed,
+            };
+            let fee = settle_fee(runtime, config, &operation, 0).unwrap_or_default();
+            (status, fee)
+        }
+        Ok(operation) => return Ok(operation),
+        Err(rejection) if is_unsigned(&rejection) => return Err(()),
+        Err(rejection) => (Status::Rejected(rejection), 0),
+    };
+    let receipt = Receipt {
+        status,
+        level,
+        fee,
+        events: Vec::default(),
+    };
+    let _ = runtime.save_receipt(&hash, &receipt, config.receipt_retention);
+    Err(())
+}
+
+impl FromRawInput for RawExternalOperation {
+    fn from_raw_input<R: Runtime + Database<Bincode> + Hasher + Verifier>(
+        runtime: &mut R,
+        raw_input: &RawInput,
+    ) -> Result<Self, ()> {
+        let unverified = try_from_bytes(&raw_input.payload)?;
+        let config = OperationConfig::load(runtime)?;
+        verify_or_reject(runtime, raw_input.level, &config, unverified)
+    }
+
+    /// Verifies the operations of the input, only once for all the services
+    fn from_raw_input_batch<R: Runtime + Database<Bincode> + Hasher + Verifier>(
+        runtime: &mut R,
+        raw_input: &RawInput,
+    ) -> Vec<Result<Self, ()>> {
+        match CurrentInput::is_verified(runtime, raw_input.level, raw_input.id) {
+            Ok(true) => {
+                let operations = decode_input(&raw_input.payload).unwrap_or_default();
+                return operations
+                    .into_iter()
+                    .enumerate()
+                    .map(
+                        |(index, operation)| match CurrentInput::load_processed(runtime, index) {
+                            Ok(Some(_)) => operation,
+                            _ => Err(()),
+                        },
+                    )
+                    .collect();
+            }
+            Ok(false) => {}
+            Err(()) => return vec![Err(())],
+        }
+
+        let operations = match (
+            try_from_batch(&raw_input.payload),
+            OperationConfig::load(runtime),
+        ) {
+            // The operations are verified in order, so a signer can send several operations in a batch
+            (Ok(operations), Ok(config)) => operations
+                .into_iter()
+                .map(|operation| {
+                    operation.and_then(|operation| {
+                        verify_or_reject(runtime, raw_input.level, &config, operation)
+                    })
+                })
+                .collect(),
+            (Ok(_), Err(_)) => vec![Err(())],
+            (Err(_), _) => match try_from_bytes(&raw_input.payload) {
+                Ok(_) => vec![Self::from_raw_input(runtime, raw_input)],
+                // Not an operation, there is nothing to verify
+                Err(_) => return vec![Err(())],
+            },
+        };
+
+        // The operations are not given to the services if the next ones could not reuse them
+        if CurrentInput::save(runtime, raw_input.level, raw_input.id, &operations).is_err() {
+            runtime.warn(&format!(
+                "operation: the operations of the input {} at level {} could not be saved",
+                raw_input.id, raw_input.level
+            ));
+            return vec![Err(())];
+        }
+        operations
+    }
+
+    /// Meters the gas consumed by the transitions, up to the gas limit of the operation
+    ///
+    /// The gas consumed by the previous services is deduced from the limit.
+    /// The events already pending are kept out of the receipt of the operation
+    fn processing<R: Runtime>(runtime: &mut R, input: &Input<Self>) {
+        let events_start = runtime.pending_events().unwrap_or_default();
+        let _ = <R as Database<Bincode>>::save(runtime, EVENTS_START_PATH, &events_start);
+
+        let gas_used = CurrentInput::index(runtime, &input.payload)
+            .and_then(|index| match index {
+                Some(index) => CurrentInput::load_processed(runtime, index),
+                None => Ok(None),
+            })
+            .ok()
+            .flatten()
+            .map(|processed| processed.gas_used)
+            .unwrap_or_default();
+        let gas_limit = OperationConfig::load(runtime)
+            .map(|config| config.gas_limit(input.payload.max_fee()))
+            .unwrap_or_default();
+        let _ = start_metering(runtime, gas_limit.saturating_sub(gas_used));
+    }
+
+    /// Collects the status, the gas and the events of the operation, until every service processed it
+    ///
+    /// An operation exceeding its gas limit fails
+    fn processed<R: Runtime>(runtime: &mut R, input: &Input<Self>, outcome: Outcome) {
+        let meter = stop_metering(runtime).ok().flatten().unwrap_or_default();
+        let status = match outcome {
+            _ if meter.is_exhausted() => Status::Failed,
+            Outcome::Applied => Status::Applied,
+            Outcome::Skipped => Status::Skipped,
+            Outcome::Failed => Status::Failed,
+        };
+        let events_start = <R as Database<Bincode>>::get::<usize>(runtime, EVENTS_START_PATH)
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let _ = runtime.store_delete(EVENTS_START_PATH);
+        let events = runtime.take_events_after(events_start).unwrap_or_default();
+
+        if CurrentInput::record(runtime, &input.payload, status, meter.used, events).is_err() {
+            let hash = input.payload.hash(runtime);
+            runtime.warn(&format!(
+                "operation: the processing of {} could not be saved",
+                hash
+            ));
+        }
+    }
+
+    /// Pays the fees of the operations and writes their receipts, with the events emitted by the transitions
+    fn input_processed<R: Runtime>(runtime: &mut R, input: &RawInput) {
+        match CurrentInput::is_verified(runtime, input.level, input.id) {
+            Ok(true) => {}
+            _ => return,
+        }
+        let operations = decode_input(&input.payload).unwrap_or_default();
+
+        let config = OperationConfig::load(runtime).unwrap_or_default();
+        for (index, operation) in operations.into_iter().enumerate() {
+            let (operation, processed) =
+                match (operation, CurrentInput::load_processed(runtime, index)) {
+                    (Ok(operation), Ok(Some(processed))) => (operation, processed),
+                    _ => continue,
+                };
+            let events = CurrentInput::events(runtime, index, &processed).unwrap_or_default();
+            let fee =
+                settle_fee(runtime, &config, &operation, processed.gas_used).unwrap_or_default();
+            let receipt = Receipt {
+                status: processed.status,
+                level: input.level,
+                fee,
+                events,
+            };
+            let hash = operation.hash(runtime);
+            let _ = runtime.save_receipt(&hash, &receipt, config.receipt_retention);
+        }
+        let _ = runtime.store_delete(CURRENT_INPUT_PATH);
+    }
+}
+
+/// Deterministic account to sign operations in tests
+///
+/// The same seed always gives the same account
+pub struct TestAccount {
+    secret_key: SecretKey,
+}
+
+impl TestAccount {
+    /// Creates the account from a seed, e.g. "alice"
+    pub fn new(seed: &str) -> Self {
+        let mut hasher = Blake2bVar::new(32).unwrap();
+        hasher.update(seed.as_bytes());
+        let mut buf = [0u8; 32];
+        hasher.finalize_variable(&mut buf).unwrap();
+        TestAccount {
+            secret_key: SecretKey::from_seed(buf),
+        }
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.secret_key.public_key()
+    }
+
+    pub fn address(&self) -> ImplicitAddress {
+        ImplicitAddress::from(&self.public_key())
+    }
+
+    /// Signs the operation, it never expires
+    pub fn sign_operation<R: Signer + Hasher>(
+        &self,
+        runtime: &mut R,
+        destination: &SmartRollupAddress,
+        nonce: u64,
+        payload: &[u8],
+    ) -> RawExternalOperation {
+        self.sign_operation_until(runtime, destination, nonce, u32::MAX, payload)
+    }
+
+    /// Signs the operation, it is rejected after the given level
+    pub fn sign_operation_until<R: Signer + Hasher>(
+        &self,
+        runtime: &mut R,
+        destination: &SmartRollupAddress,
+        nonce: u64,
+        valid_until_level: u32,
+        payload: &[u8],
+    ) -> RawExternalOperation {
+        let content = UnsignedOperation {
+            valid_until_level,
+            ..UnsignedOperation::new(destination.clone(), self.address(), nonce, payload)
+        };
+        self.sign(runtime, content)
+    }
+
+    /// Signs any operation, for instance on behalf of another account
+    pub fn sign<R: Signer + Hasher>(
+        &self,
+        runtime: &mut R,
+        content: UnsignedOperation,
+    ) -> RawExternalOperation {
+        let hash = signed_hash(runtime, &content);
+        let signature = runtime.sign(&self.secret_key, hash.as_ref());
+        RawExternalOperation {
+            content,
+            public_key: self.public_key(),
+            signature,
+            sponsor: None,
+        }
+    }
+
+    /// Sponsors the operation of another account
+    ///
+    /// The nonce is the one of the sponsor, the operation should not be sponsored yet
+    pub fn sponsor_operation<R: Signer + Hasher>(
+        &self,
+        runtime: &mut R,
+        operation: &RawExternalOperation,
+        nonce: u64,
+    ) -> RawExternalOperation {
+        self.sponsor_on_behalf_of(runtime, &self.address(), operation, nonce)
+    }
+
+    /// Sponsors the operation of another account, paid by the given account
+    pub fn sponsor_on_behalf_of<R: Signer + Hasher>(
+        &self,
+        runtime: &mut R,
+        account: &ImplicitAddress,
+        operation: &RawExternalOperation,
+        nonce: u64,
+    ) -> RawExternalOperation {
+        assert!(operation.sponsor.is_none(), "already sponsored operation");
+        let hash = sponsor_signed_hash(runtime, account, nonce, operation);
+        let signature = runtime.sign(&self.secret_key, hash.as_ref());
+        RawExternalOperation {
+            sponsor: Some(Sponsor {
+                account: account.clone(),
+                public_key: self.public_key(),
                 nonce,
                 signature,
-                payload,
-            })
+            }),
+            ..operation.clone()
         }
-        _ => Err(()),
     }
-}
 
-impl FromRawInput for RawExternalOperation {
-    fn from_raw_input<R: Database<Bincode> + Hasher + Verifier>(
+    /// Signs the operation and returns its encoded bytes, ready to be added to the inbox
+    pub fn operation<R: Signer + Hasher>(
+        &self,
         runtime: &mut R,
-        raw_input: &crate::core::RawInput,
-    ) -> Result<Self, ()> {
-        let unverified = try_from_bytes(&raw_input.payload)?;
-
-        // TODO: I would prefer to save the address and not the public key
-        let user_nonce_path = format!("/account-nonce/{}", unverified.public_key.to_string());
-        let user_nonce = runtime
-            .get::<u64>(&user_nonce_path)
-            .map_err(|_| ())?
-            .unwrap_or_default();
+        destination: &SmartRollupAddress,
+        nonce: u64,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        self.sign_operation(runtime, destination, nonce, payload)
+            .to_bytes()
+    }
+}
 
-        let operation_nonce = unverified.nonce;
+///////// More convenient type
 
-        if operation_nonce != user_nonce + 1 {
-            return Err(());
-        }
+/// Payload of a verified operation, decoded with the codec C
+///
+/// The transition is executed only if the payload can be decoded
+pub struct Signed<T, C = Bincode>
+where
+    C: Codec<T>,
+{
+    source: ImplicitAddress,
+    nonce: u64,
+    level: u32,
+    payload: T,
+    codec: PhantomData<C>,
+}
 
-        // Verifying the signature
+impl<T, C> Signed<T, C>
+where
+    C: Codec<T>,
+{
+    /// The account on behalf of which the operation is signed
+    pub fn source(&self) -> &ImplicitAddress {
+        &self.source
+    }
 
-        let hash = {
-            let mut nonce = unverified.nonce.to_be_bytes().to_vec();
-            let mut address = unverified.destination.as_bytes().to_vec();
-            let mut payload_hash = runtime.hash_512(&unverified.payload).as_ref().to_vec();
-            let mut to_hash = Vec::default();
-            to_hash.append(&mut nonce);
-            to_hash.append(&mut address);
-            to_hash.append(&mut payload_hash);
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
 
-            runtime.hash_512(&to_hash)
-        };
-        let signature = &unverified.signature;
-        let public_key = &unverified.public_key;
-        runtime.verify_signature(signature, public_key, hash.as_ref());
+    /// Level of the inbox containing the operation
+    pub fn level(&self) -> u32 {
+        self.level
+    }
 
-        // Save the new nonce if the signature is correct
-        let _ = runtime.save(&user_nonce_path, &operation_nonce);
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
 
-        // And then the operation is a verified one
-        Ok(unverified)
+    pub fn into_payload(self) -> T {
+        self.payload
     }
 }
 
-///////// More convenient type
+impl<T, C, S> FromInput<RawExternalOperation, S> for Signed<T, C>
+where
+    C: Codec<T>,
+{
+    fn from_input<R: Runtime>(
+        _: &mut R,
+        input: &Input<RawExternalOperation>,
+        _: &S,
+    ) -> Result<Self, ()> {
+        let operation = &input.payload;
+        let payload = C::decode(operation.payload())?;
+        Ok(Signed {
+            source: operation.source(),
+            nonce: operation.nonce(),
+            level: input.level,
+            payload,
+            codec: PhantomData,
+        })
+    }
+}
 
+/// Payload of an operation encoded in json
+///
+/// Signed<T, Json> also gives the source of the operation
 pub struct Json<P>
 where
     P: DeserializeOwned,
@@ -135,13 +1298,12 @@ impl<P, S> FromInput<RawExternalOperation, S> for Json<P>
 where
     P: DeserializeOwned,
 {
-    fn from_input<R: crate::core::Runtime>(
+    fn from_input<R: Runtime>(
         _: &mut R,
-        input: &crate::core::Input<RawExternalOperation>,
+        input: &Input<RawExternalOperation>,
         _: &S,
     ) -> Result<Self, ()> {
-        // TODO: find a better serialization protocol;
-        let bytes = input.payload.payload.clone();
+        let bytes = input.payload.payload().to_vec();
         let string = String::from_utf8(bytes).map_err(|_| ())?;
         let payload = serde_json_wasm::from_str::<P>(&string).map_err(|_| ())?;
         Ok(Json { payload })
@@ -151,11 +1313,29 @@ where
 #[cfg(test)]
 mod tests {
 
-    use serde::Deserialize;
+    use proptest::{collection::vec, prelude::*};
+    use serde::{Deserialize, Serialize};
 
-    use crate::core::{MockRuntime, Runtime, Service};
+    use crate::core::{Application, MockRuntime, Runtime, Service, MAX_FILE_CHUNK_SIZE};
+    use crate::encoding::{
+        address::{ImplicitAddress, SmartRollupAddress},
+        b58::B58Check,
+        codec::{Bincode, Codec, Json as JsonCodec},
+    };
+    use crate::plugins::{
+        crypto::{PublicKey, SecretKey, Signature, Signer, Verifier},
+        events::{Event, Events},
+    };
+    use crate::services::{
+        account::{KeyUpdate, SessionKey},
+        fee::Fees,
+        receipt::{Receipt, Receipts, Rejection, Status},
+    };
 
-    use super::{try_from_bytes, Json, RawExternalOperation};
+    use super::{
+        signed_hash, try_from_batch, try_from_bytes, Json, OperationConfig, RawExternalOperation,
+        Signed, TestAccount, UnsignedOperation,
+    };
 
     fn transition<R: Runtime>(rt: &mut R, _: RawExternalOperation) {
         rt.write_debug("Hello {source}")
@@ -179,33 +1359,38 @@ mod tests {
 
     #[test]
     fn deserialization() {
-        let destination = "src13mudsG5iD2E7UqzkWbHR1yPkAqmrmtD9NXc57agXVM8zMbxnbq";
+        let destination = "sr1UNDWPUYVeomgG15wn5jSw689EJ4RNnVQa";
+        let account = "tz1QFD9WqLWZmmAuqnnTPPUjfauitYEWdshv";
+        let kind = 0x0A;
         let public_key = "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCfK";
-        let nonce: u64 = 1;
+        // The nonce and the payload contain some new lines
+        let nonce: u64 = 0x0A0A;
+        let valid_until_level: u32 = 0x0A;
+        let max_fee: u64 = 0x0A0A0A;
         let signature = "edsigtuU5nUqBniorqTFXFixkG6ZkfvEPrfc9aT9DnMAeims2AX2yjpgYaedXBoKzAGHE3ZXSi1hZz6piZ3itTE7f2F4FoaxXtM";
         let payload = vec![0xA, 0x01, 0x01, 0xA, 0x1, 0x1, 0xA];
         let message = {
-            let mut msg = vec![0x1];
+            let mut msg = vec![0x01, 0x00, 0x04];
 
-            let mut destination = destination.as_bytes().to_vec();
-            let mut public_key = public_key.as_bytes().to_vec();
-            let mut nonce = nonce.to_be_bytes().to_vec();
-            let mut signature = signature.as_bytes().to_vec();
-            let mut payload = payload.clone();
+            let destination = SmartRollupAddress::from_b58check(destination).unwrap();
+            let account = ImplicitAddress::from_b58check(account).unwrap();
+            let public_key = PublicKey::from_b58check(public_key).unwrap().to_bytes();
+            let signature = Signature::from_b58check(signature).unwrap().to_bytes();
 
-            msg.append(&mut destination);
-            msg.push(0xA);
-
-            msg.append(&mut public_key);
-            msg.push(0xA);
-
-            msg.append(&mut nonce);
-            msg.push(0xA);
-
-            msg.append(&mut signature);
-            msg.push(0xA);
-
-            msg.append(&mut payload);
+            msg.extend(destination.as_bytes());
+            msg.push(0x00);
+            msg.extend(account.as_bytes());
+            msg.push(kind);
+            msg.push(0x00);
+            msg.extend((public_key.len() as u32).to_be_bytes());
+            msg.extend(public_key);
+            msg.extend(nonce.to_be_bytes());
+            msg.extend(valid_until_level.to_be_bytes());
+            msg.extend(max_fee.to_be_bytes());
+            msg.extend((signature.len() as u32).to_be_bytes());
+            msg.extend(signature);
+            msg.extend((payload.len() as u32).to_be_bytes());
+            msg.extend(&payload);
 
             msg
         };
@@ -215,10 +1400,972 @@ mod tests {
         assert!(msg.is_ok());
         let msg = msg.unwrap();
 
-        assert_eq!(msg.destination, destination);
-        // assert_eq!(msg.public_key, public_key);
-        assert_eq!(msg.nonce, nonce);
-        // assert_eq!(msg.signature, signature);
-        assert_eq!(msg.payload, payload);
+        assert_eq!(msg.destination().to_b58check(), destination);
+        assert_eq!(msg.source().to_b58check(), account);
+        assert_eq!(ImplicitAddress::from(msg.public_key()), msg.source());
+        assert_eq!(msg.kind(), kind);
+        assert_eq!(msg.nonce(), nonce);
+        assert_eq!(msg.valid_until_level(), valid_until_level);
+        assert_eq!(msg.max_fee(), max_fee);
+        assert_eq!(msg.signature.to_b58check(), signature);
+        assert_eq!(msg.payload(), payload);
+        assert_eq!(msg.to_bytes(), message);
+    }
+
+    #[test]
+    fn test_reject_malformed_operation() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let bytes =
+            TestAccount::new("alice").operation(&mut runtime, &destination, 1, "hello".as_bytes());
+
+        // Unknown version
+        let mut unknown_version = bytes.clone();
+        unknown_version[2] = 0x01;
+        assert!(try_from_bytes(&unknown_version).is_err());
+
+        // Unknown curve of the account
+        let mut unknown_curve = bytes.clone();
+        unknown_curve[23] = 0x04;
+        assert!(try_from_bytes(&unknown_curve).is_err());
+
+        // Unknown curve of the public key
+        let mut unknown_curve = bytes.clone();
+        unknown_curve[45] = 0x04;
+        assert!(try_from_bytes(&unknown_curve).is_err());
+
+        // Trailing bytes
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        assert!(try_from_bytes(&trailing).is_err());
+    }
+
+    /// Encodes an operation signed with the given seed and decodes it
+    fn round_trip(
+        seed: [u8; 32],
+        destination: [u8; 20],
+        kind: u8,
+        nonce: u64,
+        level: u32,
+        max_fee: u64,
+        payload: &[u8],
+    ) {
+        let mut runtime = MockRuntime::default();
+        let secret_key = SecretKey::from_seed(seed);
+        let destination = SmartRollupAddress::from(destination);
+        let content = UnsignedOperation {
+            destination,
+            account: ImplicitAddress::from(&secret_key.public_key()),
+            kind,
+            nonce,
+            valid_until_level: level,
+            max_fee,
+            payload: payload.to_vec(),
+        };
+        let hash = signed_hash(&mut runtime, &content);
+        let operation = RawExternalOperation {
+            content,
+            public_key: secret_key.public_key(),
+            signature: runtime.sign(&secret_key, hash.as_ref()),
+            sponsor: None,
+        };
+
+        let bytes = operation.to_bytes();
+        assert_eq!(try_from_bytes(&bytes), Ok(operation));
+
+        // Any truncated message is rejected
+        for size in 0..bytes.len() {
+            assert!(try_from_bytes(&bytes[..size]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip([1; 32], [0; 20], 0, 0, 0, 0, &[]);
+        round_trip(
+            [0xA; 32], [0xA; 20], 0xA, 0x0A0A0A0A, 0x0A0A, 0x0A0A0A, &[0xA; 64],
+        );
+        round_trip(
+            [0xFF; 32],
+            [0xFF; 20],
+            0xFF,
+            u64::MAX,
+            u32::MAX,
+            u64::MAX,
+            &[0xFF; 1024],
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip_property(
+            seed in any::<[u8; 32]>().prop_filter("non zero seed", |seed| seed != &[0; 32]),
+            destination in any::<[u8; 20]>(),
+            kind in any::<u8>(),
+            nonce in any::<u64>(),
+            level in any::<u32>(),
+            max_fee in any::<u64>(),
+            payload in vec(any::<u8>(), 0..512),
+        ) {
+            round_trip(seed, destination, kind, nonce, level, max_fee, &payload);
+        }
+
+        #[test]
+        fn test_decode_arbitrary_bytes(bytes in vec(any::<u8>(), 0..512)) {
+            // Decoding random bytes should never panic
+            let _ = try_from_bytes(&bytes);
+        }
+    }
+
+    fn is_signed(runtime: &mut MockRuntime, operation: &RawExternalOperation) -> bool {
+        let hash = signed_hash(runtime, &operation.content);
+        runtime.verify_signature(&operation.signature, &operation.public_key, hash.as_ref())
+    }
+
+    #[test]
+    fn test_account_is_deterministic() {
+        assert_eq!(
+            TestAccount::new("alice").address(),
+            TestAccount::new("alice").address()
+        );
+        assert_ne!(
+            TestAccount::new("alice").address(),
+            TestAccount::new("bob").address()
+        );
+    }
+
+    #[test]
+    fn test_signed_operation() {
+        let mut runtime = MockRuntime::default();
+        let destination =
+            SmartRollupAddress::from_b58check("sr1UNDWPUYVeomgG15wn5jSw689EJ4RNnVQa").unwrap();
+        let alice = TestAccount::new("alice");
+
+        let bytes = alice.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        let operation = try_from_bytes(&bytes).unwrap();
+
+        assert_eq!(operation.source(), alice.address());
+        assert_eq!(operation.destination(), &destination);
+        assert_eq!(operation.nonce(), 1);
+        assert_eq!(operation.payload(), "hello".as_bytes());
+        assert!(is_signed(&mut runtime, &operation));
+    }
+
+    #[test]
+    fn test_forged_operation() {
+        let mut runtime = MockRuntime::default();
+        let destination =
+            SmartRollupAddress::from_b58check("sr1UNDWPUYVeomgG15wn5jSw689EJ4RNnVQa").unwrap();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        // Bob signs an operation on behalf of alice
+        let bytes = bob.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        let mut operation = try_from_bytes(&bytes).unwrap();
+        operation.public_key = alice.public_key();
+        assert!(!is_signed(&mut runtime, &operation));
+
+        // The payload has been changed after the signature
+        let bytes = alice.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        let mut operation = try_from_bytes(&bytes).unwrap();
+        operation.content.payload = "goodbye".as_bytes().to_vec();
+        assert!(!is_signed(&mut runtime, &operation));
+    }
+
+    fn greet<R: Runtime>(rt: &mut R, operation: RawExternalOperation) {
+        rt.write_debug(&format!("Hello {}", operation.source()));
+    }
+
+    fn run(runtime: &mut MockRuntime) {
+        let mut service = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        service.register(greet);
+        Application::new(runtime).service(service).run();
+    }
+
+    #[test]
+    fn test_accept_signed_operation() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let operation = alice.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        runtime.add_input(operation);
+        run(&mut runtime);
+
+        assert_eq!(
+            runtime.stdout().last(),
+            Some(&format!("Hello {}", alice.address()).as_str())
+        );
+    }
+
+    #[test]
+    fn test_reject_forged_signature() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        // Bob signs an operation on behalf of alice
+        let bytes = bob.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        let mut operation = try_from_bytes(&bytes).unwrap();
+        operation.public_key = alice.public_key();
+        runtime.add_input(operation.to_bytes());
+        run(&mut runtime);
+
+        assert!(!runtime
+            .stdout()
+            .iter()
+            .any(|line| line.starts_with("Hello")));
+
+        // The nonce of alice has not been burnt
+        let operation = alice.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        runtime.add_input(operation);
+        run(&mut runtime);
+
+        assert_eq!(
+            runtime.stdout().last(),
+            Some(&format!("Hello {}", alice.address()).as_str())
+        );
+    }
+
+    #[test]
+    fn test_reject_wrong_destination() {
+        let mut runtime = MockRuntime::default();
+        let destination = SmartRollupAddress::from([1; 20]);
+        let alice = TestAccount::new("alice");
+
+        let operation = alice.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        runtime.add_input(operation);
+        run(&mut runtime);
+
+        assert!(!runtime
+            .stdout()
+            .iter()
+            .any(|line| line.starts_with("Hello")));
+    }
+
+    #[test]
+    fn test_reject_replayed_nonce() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let operation = alice.operation(&mut runtime, &destination, 1, "hello".as_bytes());
+        runtime.add_input(operation.clone()).add_input(operation);
+        run(&mut runtime);
+
+        let greetings = runtime
+            .stdout()
+            .iter()
+            .filter(|line| line.starts_with("Hello"))
+            .count();
+        assert_eq!(greetings, 1);
+    }
+
+    #[test]
+    fn test_reject_expired_operation() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let operation =
+            alice.sign_operation_until(&mut runtime, &destination, 1, 10, "hello".as_bytes());
+        runtime.add_input_at_level(11, operation.to_bytes());
+        run(&mut runtime);
+
+        assert!(!runtime
+            .stdout()
+            .iter()
+            .any(|line| line.starts_with("Hello")));
+
+        runtime.add_input_at_level(10, operation.to_bytes());
+        run(&mut runtime);
+
+        assert_eq!(
+            runtime.stdout().last(),
+            Some(&format!("Hello {}", alice.address()).as_str())
+        );
+    }
+
+    #[test]
+    fn test_nonce_window() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        assert!(OperationConfig {
+            nonce_window: 65,
+            ..OperationConfig::default()
+        }
+        .save(&mut runtime)
+        .is_err());
+        OperationConfig {
+            nonce_window: 4,
+            ..OperationConfig::default()
+        }
+        .save(&mut runtime)
+        .unwrap();
+
+        // 2 is replayed, 8 is too far ahead of 3, 3 is replayed
+        let operations = [3, 1, 2, 2, 8, 7, 3, 4]
+            .iter()
+            .map(|nonce| alice.sign_operation(&mut runtime, &destination, *nonce, &[]))
+            .collect::<Vec<_>>();
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+        run(&mut runtime);
+
+        let greetings = runtime
+            .stdout()
+            .iter()
+            .filter(|line| line.starts_with("Hello"))
+            .count();
+        assert_eq!(greetings, 5);
+
+        // 2 is now outside of the window
+        let operation = alice.operation(&mut runtime, &destination, 2, &[]);
+        runtime.add_input(operation);
+        run(&mut runtime);
+
+        let greetings = runtime
+            .stdout()
+            .iter()
+            .filter(|line| line.starts_with("Hello"))
+            .count();
+        assert_eq!(greetings, 5);
+    }
+
+    #[test]
+    fn test_set_nonce_window() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let operations = [1, 2]
+            .iter()
+            .map(|nonce| alice.sign_operation(&mut runtime, &destination, *nonce, &[]))
+            .collect::<Vec<_>>();
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+        run(&mut runtime);
+
+        OperationConfig {
+            nonce_window: 4,
+            ..OperationConfig::default()
+        }
+        .save(&mut runtime)
+        .unwrap();
+
+        // The nonces used without a window cannot be replayed
+        let operations = [1, 2, 3]
+            .iter()
+            .map(|nonce| alice.sign_operation(&mut runtime, &destination, *nonce, &[]))
+            .collect::<Vec<_>>();
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+        run(&mut runtime);
+
+        let greetings = runtime
+            .stdout()
+            .iter()
+            .filter(|line| line.starts_with("Hello"))
+            .count();
+        assert_eq!(greetings, 3);
+    }
+
+    fn greet_with_event<R: Runtime>(rt: &mut R, operation: RawExternalOperation) {
+        rt.emit("greeted", &operation.source()).unwrap();
+    }
+
+    #[test]
+    fn test_receipts() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let applied = alice.sign_operation(&mut runtime, &destination, 1, &[]);
+        let rejected = alice.sign_operation(&mut runtime, &destination, 3, &[]);
+        let batch = RawExternalOperation::batch_to_bytes(&[applied.clone(), rejected.clone()]);
+        runtime.add_input_at_level(7, batch);
+
+        let mut service = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        service.register(greet_with_event);
+        Application::new(&mut runtime).service(service).run();
+
+        let hash = applied.hash(&mut runtime);
+        assert_eq!(
+            runtime.receipt(&hash),
+            Ok(Some(Receipt {
+                status: Status::Applied,
+                level: 7,
+                fee: 0,
+                events: vec![Event {
+                    name: "greeted".to_string(),
+                    data: format!("\"{}\"", alice.address()),
+                }],
+            }))
+        );
+
+        let hash = rejected.hash(&mut runtime);
+        assert_eq!(
+            runtime.receipt(&hash),
+            Ok(Some(Receipt {
+                status: Status::Rejected(Rejection::InvalidNonce),
+                level: 7,
+                fee: 0,
+                events: Vec::default(),
+            }))
+        );
+    }
+
+    fn receive<R: Runtime>(rt: &mut R) {
+        rt.emit("received", &()).unwrap();
+    }
+
+    #[test]
+    fn test_events_outside_of_operations() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let operation = alice.sign_operation(&mut runtime, &destination, 1, &[]);
+        runtime.add_input(operation.to_bytes());
+
+        let mut service = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        service.register(greet_with_event);
+        Application::new(&mut runtime)
+            .register(receive)
+            .service(service)
+            .run();
+
+        // The event of the input is not in the receipt of the operation
+        let hash = operation.hash(&mut runtime);
+        let receipt = runtime.receipt(&hash).unwrap().unwrap();
+        let names = receipt
+            .events
+            .iter()
+            .map(|event| event.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["greeted"]);
+
+        // The pending event of the input is dropped once the input is processed
+        assert_eq!(runtime.pending_events(), Ok(0));
+    }
+
+    fn count_greetings(runtime: &MockRuntime, account: &TestAccount) -> usize {
+        let greeting = format!("Hello {}", account.address());
+        runtime
+            .stdout()
+            .iter()
+            .filter(|line| **line == greeting)
+            .count()
+    }
+
+    #[test]
+    fn test_sponsored_operation() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        let operation = bob.sign_operation(&mut runtime, &destination, 1, &[]);
+        let sponsored = alice.sponsor_operation(&mut runtime, &operation, 1);
+        assert_eq!(try_from_bytes(&sponsored.to_bytes()), Ok(sponsored.clone()));
+        assert_eq!(sponsored.source(), bob.address());
+        assert_eq!(sponsored.sponsor(), Some(alice.address()));
+        assert_eq!(sponsored.fee_payer(), alice.address());
+        assert_eq!(operation.fee_payer(), bob.address());
+
+        // The same operation of bob cannot be sponsored twice
+        let replayed = alice.sponsor_operation(&mut runtime, &operation, 2);
+        let batch = RawExternalOperation::batch_to_bytes(&[sponsored.clone(), replayed.clone()]);
+        runtime.add_input(batch);
+        run(&mut runtime);
+
+        // The transition sees bob as the signer
+        assert_eq!(count_greetings(&runtime, &bob), 1);
+        assert_eq!(count_greetings(&runtime, &alice), 0);
+
+        let hash = replayed.hash(&mut runtime);
+        let receipt = runtime.receipt(&hash).unwrap().unwrap();
+        assert_eq!(receipt.status, Status::Rejected(Rejection::InvalidNonce));
+
+        // The nonce of alice has been consumed
+        let operation = alice.operation(&mut runtime, &destination, 1, &[]);
+        runtime.add_input(operation);
+        run(&mut runtime);
+        assert_eq!(count_greetings(&runtime, &alice), 0);
+    }
+
+    #[test]
+    fn test_rotated_sponsor() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let rotated = TestAccount::new("alice-2");
+        let bob = TestAccount::new("bob");
+
+        let add_key = UnsignedOperation::key_update(
+            destination.clone(),
+            alice.address(),
+            1,
+            &KeyUpdate::AddKey(rotated.public_key()),
+        )
+        .unwrap();
+        let add_key = alice.sign(&mut runtime, add_key);
+
+        // The new key of alice sponsors the operation of bob, alice pays for it
+        let operation = bob.sign_operation(&mut runtime, &destination, 1, &[]);
+        let sponsored = rotated.sponsor_on_behalf_of(&mut runtime, &alice.address(), &operation, 2);
+        assert_eq!(sponsored.fee_payer(), alice.address());
+
+        // A key which is not one of alice cannot sponsor on behalf of alice
+        let operation = bob.sign_operation(&mut runtime, &destination, 2, &[]);
+        let carol = TestAccount::new("carol");
+        let unauthorized =
+            carol.sponsor_on_behalf_of(&mut runtime, &alice.address(), &operation, 3);
+
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&[
+            add_key,
+            sponsored.clone(),
+            unauthorized.clone(),
+        ]));
+        run(&mut runtime);
+
+        assert_eq!(count_greetings(&runtime, &bob), 1);
+        let hash = sponsored.hash(&mut runtime);
+        let receipt = runtime.receipt(&hash).unwrap().unwrap();
+        assert_eq!(receipt.status, Status::Applied);
+        let hash = unauthorized.hash(&mut runtime);
+        assert_eq!(runtime.receipt(&hash), Ok(None));
+    }
+
+    #[test]
+    fn test_reject_forged_sponsor() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        let operation = bob.sign_operation(&mut runtime, &destination, 1, &[]);
+        let sponsored = alice.sponsor_operation(&mut runtime, &operation, 1);
+        let carol = TestAccount::new("carol");
+        let mut forged = sponsored.clone();
+        if let Some(sponsor) = forged.sponsor.as_mut() {
+            sponsor.public_key = carol.public_key();
+        }
+        runtime.add_input(forged.to_bytes());
+        run(&mut runtime);
+
+        assert_eq!(count_greetings(&runtime, &bob), 0);
+        // The forged operation does not get a receipt
+        let hash = forged.hash(&mut runtime);
+        assert_eq!(runtime.receipt(&hash), Ok(None));
+
+        // A sponsored operation cannot be sponsored again
+        let encoded = sponsored.to_bytes();
+        // external tag, sponsored tag, version, length and operation of the user
+        let sponsor_fields = &encoded[7 + operation.to_bytes().len() - 1..];
+        let mut nested = vec![0x01, 0x02, 0x04];
+        nested.extend((encoded.len() as u32 - 1).to_be_bytes());
+        nested.extend(&encoded[1..]);
+        nested.extend(sponsor_fields);
+        assert!(try_from_bytes(&nested).is_err());
+
+        // Whereas the same fields around the operation of the user are valid
+        let mut valid = vec![0x01, 0x02, 0x04];
+        valid.extend((operation.to_bytes().len() as u32 - 1).to_be_bytes());
+        valid.extend(&operation.to_bytes()[1..]);
+        valid.extend(sponsor_fields);
+        assert_eq!(try_from_bytes(&valid), Ok(sponsored));
+    }
+
+    #[test]
+    fn test_self_sponsored_operation() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let operation = alice.sign_operation(&mut runtime, &destination, 1, &[]);
+        let sponsored = alice.sponsor_operation(&mut runtime, &operation, 2);
+        runtime.add_input(sponsored.to_bytes());
+        run(&mut runtime);
+        assert_eq!(count_greetings(&runtime, &alice), 1);
+
+        let operation = alice.operation(&mut runtime, &destination, 3, &[]);
+        runtime.add_input(operation);
+        run(&mut runtime);
+        assert_eq!(count_greetings(&runtime, &alice), 2);
+    }
+
+    #[test]
+    fn test_account_keys() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let rotated = TestAccount::new("alice-2");
+        let session = TestAccount::new("alice-session");
+
+        let add_key = UnsignedOperation::key_update(
+            destination.clone(),
+            alice.address(),
+            1,
+            &KeyUpdate::AddKey(rotated.public_key()),
+        )
+        .unwrap();
+        let add_key = alice.sign(&mut runtime, add_key);
+
+        // The new key signs on behalf of alice
+        let content = UnsignedOperation::new(destination.clone(), alice.address(), 2, &[]);
+        let rotated_operation = rotated.sign(&mut runtime, content);
+
+        let add_session = UnsignedOperation::key_update(
+            destination.clone(),
+            alice.address(),
+            3,
+            &KeyUpdate::AddSessionKey(SessionKey {
+                public_key: session.public_key(),
+                expires_at: 100,
+                kinds: vec![1],
+            }),
+        )
+        .unwrap();
+        let add_session = rotated.sign(&mut runtime, add_session);
+
+        // The session key can only sign operations of kind 1
+        let content = UnsignedOperation::new(destination.clone(), alice.address(), 4, &[]);
+        let unauthorized = session.sign(&mut runtime, content.clone());
+        let session_operation =
+            session.sign(&mut runtime, UnsignedOperation { kind: 1, ..content });
+
+        let operations = [
+            add_key.clone(),
+            rotated_operation,
+            add_session,
+            unauthorized.clone(),
+            session_operation,
+        ];
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+        run(&mut runtime);
+
+        // The updates of keys are not given to the transitions
+        assert_eq!(count_greetings(&runtime, &alice), 2);
+
+        let hash = add_key.hash(&mut runtime);
+        let receipt = runtime.receipt(&hash).unwrap().unwrap();
+        assert_eq!(receipt.status, Status::Applied);
+
+        // The operations signed by an unauthorized key do not get a receipt
+        let hash = unauthorized.hash(&mut runtime);
+        assert_eq!(runtime.receipt(&hash), Ok(None));
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Transfer {
+        to: ImplicitAddress,
+        amount: u64,
+    }
+
+    fn transfer<R: Runtime>(rt: &mut R, transfer: Signed<Transfer>) {
+        let Transfer { to, amount } = transfer.payload();
+        rt.write_debug(&format!(
+            "{} sends {} to {} with nonce {}",
+            transfer.source(),
+            amount,
+            to,
+            transfer.nonce()
+        ));
+    }
+
+    fn json_transfer<R: Runtime>(rt: &mut R, transfer: Signed<Transfer, JsonCodec>) {
+        let transfer = transfer.into_payload();
+        rt.write_debug(&format!("json {}", transfer.amount));
+    }
+
+    #[test]
+    fn test_signed_extractor() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        let payload = Transfer {
+            to: bob.address(),
+            amount: 10,
+        };
+        let bincode = Bincode::encode(&payload).unwrap();
+        let json = <JsonCodec as Codec<Transfer>>::encode(&payload).unwrap();
+        let operations = [
+            alice.sign_operation(&mut runtime, &destination, 1, &bincode),
+            alice.sign_operation(&mut runtime, &destination, 2, &json),
+        ];
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+
+        let mut service = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        service.register(transfer).register(json_transfer);
+        Application::new(&mut runtime).service(service).run();
+
+        assert_eq!(
+            runtime.stdout(),
+            vec![
+                format!(
+                    "{} sends 10 to {} with nonce 1",
+                    alice.address(),
+                    bob.address()
+                )
+                .as_str(),
+                "json 10"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        let alice_1 = alice.sign_operation(&mut runtime, &destination, 1, "1".as_bytes());
+        let alice_2 = alice.sign_operation(&mut runtime, &destination, 2, "2".as_bytes());
+        let bob_1 = bob.sign_operation(&mut runtime, &destination, 1, "3".as_bytes());
+        // Replayed operation
+        let bob_1_bis = bob_1.clone();
+        // Bob signs an operation on behalf of alice
+        let mut forged = bob.sign_operation(&mut runtime, &destination, 3, "4".as_bytes());
+        forged.public_key = alice.public_key();
+
+        let batch =
+            RawExternalOperation::batch_to_bytes(&[alice_1, bob_1, forged, bob_1_bis, alice_2]);
+        runtime.add_input(batch);
+        run(&mut runtime);
+
+        let greetings = runtime
+            .stdout()
+            .iter()
+            .filter(|line| line.starts_with("Hello"))
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            greetings,
+            vec![
+                format!("Hello {}", alice.address()),
+                format!("Hello {}", bob.address()),
+                format!("Hello {}", alice.address()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_operation_in_batch() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        let operation = alice.sign_operation(&mut runtime, &destination, 1, "1".as_bytes());
+        let mut batch = RawExternalOperation::batch_to_bytes(&[operation]);
+        // An operation of one byte
+        batch.extend([0, 0, 0, 1, 0xFF]);
+
+        let operations = try_from_batch(&batch).unwrap();
+        assert_eq!(operations.len(), 2);
+        assert!(operations[0].is_ok());
+        assert!(operations[1].is_err());
+
+        // The size of the last operation is wrong
+        batch.extend([0, 0, 0, 2, 0xFF]);
+        assert!(try_from_batch(&batch).is_err());
+    }
+
+    /// Consumes one unit of gas for each byte of the payload
+    fn greet_with_gas<R: Runtime>(rt: &mut R, operation: RawExternalOperation) {
+        if rt.consume_gas(operation.payload().len() as u64).is_ok() {
+            greet(rt, operation);
+        }
+    }
+
+    fn sign_with_fee(
+        runtime: &mut MockRuntime,
+        account: &TestAccount,
+        nonce: u64,
+        max_fee: u64,
+        payload: &[u8],
+    ) -> RawExternalOperation {
+        let destination = runtime.rollup_address().unwrap();
+        let content = UnsignedOperation {
+            max_fee,
+            ..UnsignedOperation::new(destination, account.address(), nonce, payload)
+        };
+        account.sign(runtime, content)
+    }
+
+    #[test]
+    fn test_fees() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice");
+        let collector = TestAccount::new("collector").address();
+
+        OperationConfig {
+            base_fee: 10,
+            gas_price: 2,
+            fee_collector: Some(collector.clone()),
+            ..OperationConfig::default()
+        }
+        .save(&mut runtime)
+        .unwrap();
+        runtime.credit_fee(&alice.address(), 100).unwrap();
+
+        let too_low = sign_with_fee(&mut runtime, &alice, 1, 5, &[]);
+        let too_high = sign_with_fee(&mut runtime, &alice, 1, 200, &[]);
+        // 10 + 5 * 2
+        let applied = sign_with_fee(&mut runtime, &alice, 1, 50, &[0; 5]);
+        // The gas limit is (30 - 10) / 2
+        let out_of_gas = sign_with_fee(&mut runtime, &alice, 2, 30, &[0; 20]);
+        let operations = [too_low, too_high, applied, out_of_gas];
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+
+        let mut service = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        service.register(greet_with_gas);
+        Application::new(&mut runtime).service(service).run();
+
+        assert_eq!(count_greetings(&runtime, &alice), 1);
+        assert_eq!(runtime.fee_balance(&alice.address()), Ok(50));
+        assert_eq!(runtime.fee_balance(&collector), Ok(50));
+
+        let receipts = operations
+            .iter()
+            .map(|operation| {
+                let hash = operation.hash(&mut runtime);
+                let receipt = runtime.receipt(&hash).unwrap().unwrap();
+                (receipt.status, receipt.fee)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            receipts,
+            vec![
+                (Status::Rejected(Rejection::FeeTooLow), 0),
+                (Status::Rejected(Rejection::InsufficientBalance), 0),
+                (Status::Applied, 20),
+                (Status::Failed, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sponsor_insufficient_balance() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        OperationConfig {
+            base_fee: 10,
+            ..OperationConfig::default()
+        }
+        .save(&mut runtime)
+        .unwrap();
+        runtime.credit_fee(&alice.address(), 10).unwrap();
+
+        let operation = sign_with_fee(&mut runtime, &bob, 1, 20, &[]);
+        let sponsored = alice.sponsor_operation(&mut runtime, &operation, 1);
+        runtime.add_input(sponsored.to_bytes());
+        run(&mut runtime);
+
+        let hash = sponsored.hash(&mut runtime);
+        let receipt = runtime.receipt(&hash).unwrap().unwrap();
+        assert_eq!(
+            receipt.status,
+            Status::Rejected(Rejection::InsufficientBalance)
+        );
+
+        // The rejected operation has not consumed the nonce of alice
+        let operation = sign_with_fee(&mut runtime, &alice, 1, 10, &[]);
+        runtime.add_input(operation.to_bytes());
+        run(&mut runtime);
+        assert_eq!(count_greetings(&runtime, &alice), 1);
+    }
+
+    #[test]
+    fn test_several_services() {
+        let mut runtime = MockRuntime::default();
+        let alice = TestAccount::new("alice");
+        let collector = TestAccount::new("collector").address();
+
+        OperationConfig {
+            base_fee: 10,
+            gas_price: 1,
+            fee_collector: Some(collector.clone()),
+            ..OperationConfig::default()
+        }
+        .save(&mut runtime)
+        .unwrap();
+        runtime.credit_fee(&alice.address(), 100).unwrap();
+
+        // The gas limit is 10, shared by the services
+        let applied = sign_with_fee(&mut runtime, &alice, 1, 20, &[0; 4]);
+        let out_of_gas = sign_with_fee(&mut runtime, &alice, 2, 20, &[0; 6]);
+        let operations = [applied, out_of_gas];
+        runtime.add_input(RawExternalOperation::batch_to_bytes(&operations));
+
+        let mut events = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        events.register(greet_with_event);
+        let mut gas = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        gas.register(greet_with_gas);
+        let mut more_gas = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        more_gas.register(greet_with_gas);
+        Application::new(&mut runtime)
+            .service(events)
+            .service(gas)
+            .service(more_gas)
+            .run();
+
+        // The operations are verified once, so every service processes them
+        assert_eq!(count_greetings(&runtime, &alice), 3);
+
+        // The fees are paid once
+        assert_eq!(runtime.fee_balance(&alice.address()), Ok(62));
+        assert_eq!(runtime.fee_balance(&collector), Ok(38));
+
+        let receipts = operations
+            .iter()
+            .map(|operation| {
+                let hash = operation.hash(&mut runtime);
+                let receipt = runtime.receipt(&hash).unwrap().unwrap();
+                (receipt.status, receipt.fee, receipt.events.len())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            receipts,
+            vec![(Status::Applied, 18, 1), (Status::Failed, 20, 1)]
+        );
+    }
+
+    #[test]
+    fn test_large_batch() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+
+        // The batch is larger than what can be written at once in the storage
+        let operations = (1..=12)
+            .map(|nonce| alice.sign_operation(&mut runtime, &destination, nonce, &[0; 100]))
+            .collect::<Vec<_>>();
+        let batch = RawExternalOperation::batch_to_bytes(&operations);
+        assert!(batch.len() > MAX_FILE_CHUNK_SIZE);
+        runtime.add_input(batch);
+
+        let mut greeter = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        greeter.register(greet);
+        let mut events = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        events.register(greet_with_event);
+        Application::new(&mut runtime)
+            .service(greeter)
+            .service(events)
+            .run();
+
+        // The operations are not rejected as replays by the second service
+        assert_eq!(count_greetings(&runtime, &alice), 12);
+        for operation in operations.iter() {
+            let hash = operation.hash(&mut runtime);
+            let receipt = runtime.receipt(&hash).unwrap().unwrap();
+            assert_eq!(receipt.status, Status::Applied);
+            assert_eq!(receipt.events.len(), 1);
+        }
     }
 }
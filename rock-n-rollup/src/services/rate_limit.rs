@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{Input, Runtime},
+    plugins::{
+        database::{Bincode, Database},
+        logger::Logger,
+    },
+    services::operation::RawExternalOperation,
+};
+
+/// Path of the configuration of the rate limit
+const CONFIG_PATH: &str = "/rate-limit/config";
+
+/// Sender of a payload, its inputs are counted by the rate limit
+pub trait Sender {
+    /// Identifies the sender, for instance its address encoded in base58
+    fn sender(&self) -> String;
+}
+
+/// The account of the operation
+impl Sender for RawExternalOperation {
+    fn sender(&self) -> String {
+        self.source().to_string()
+    }
+}
+
+/// Configuration of the rate limit, saved in the durable storage
+///
+/// The levels are split in windows of `window` levels, each sender can send `quota` inputs per window.
+/// The rate limit is disabled with a window of 0.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    pub window: u32,
+    pub quota: u32,
+}
+
+impl RateLimitConfig {
+    pub fn load<R: Runtime>(runtime: &mut R) -> Result<RateLimitConfig, ()> {
+        let config = <R as Database<Bincode>>::get(runtime, CONFIG_PATH)?;
+        Ok(config.unwrap_or_default())
+    }
+
+    pub fn save<R: Runtime>(&self, runtime: &mut R) -> Result<(), ()> {
+        <R as Database<Bincode>>::save(runtime, CONFIG_PATH, self).map(|_| ())
+    }
+}
+
+/// Inputs of a sender in its last window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Usage {
+    /// First level of the window
+    window_start: u32,
+    count: u32,
+}
+
+fn usage_path(sender: &str) -> String {
+    format!("/rate-limit/senders/{}", sender)
+}
+
+/// Counts the input of the sender, returns false if its quota is reached
+fn count<R: Runtime>(runtime: &mut R, sender: &str, level: u32) -> Result<bool, ()> {
+    let config = RateLimitConfig::load(runtime)?;
+    if config.window == 0 {
+        return Ok(true);
+    }
+
+    let window_start = level - level % config.window;
+    let path = usage_path(sender);
+    let usage = <R as Database<Bincode>>::get::<Usage>(runtime, &path)?
+        .filter(|usage| usage.window_start == window_start)
+        .unwrap_or(Usage {
+            window_start,
+            count: 0,
+        });
+
+    if usage.count >= config.quota {
+        return Ok(false);
+    }
+
+    let usage = Usage {
+        count: usage.count + 1,
+        ..usage
+    };
+    <R as Database<Bincode>>::save(runtime, &path, &usage)?;
+    Ok(true)
+}
+
+/// Guard rejecting the inputs of a sender which reached its quota in the current window
+///
+/// The inputs are counted by each service using the guard, a rejected input is logged
+pub fn rate_limit<R: Runtime, P: Sender>(runtime: &mut R, input: &Input<P>) -> bool {
+    let sender = input.payload.sender();
+    match count(runtime, &sender, input.level) {
+        Ok(true) => true,
+        Ok(false) => {
+            runtime.warn(&format!(
+                "rate limit: {} reached its quota at level {}",
+                sender, input.level
+            ));
+            false
+        }
+        Err(()) => {
+            runtime.err(&format!(
+                "rate limit: cannot count the inputs of {}",
+                sender
+            ));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rate_limit, RateLimitConfig};
+    use crate::core::{Application, MockRuntime, Runtime, Service};
+    use crate::services::internal::{transfer_message, Transfer};
+    use crate::services::operation::{RawExternalOperation, TestAccount};
+    use tezos_smart_rollup_encoding::michelson::MichelsonBytes;
+
+    fn greet<R: Runtime>(rt: &mut R, operation: RawExternalOperation) {
+        rt.write_debug(&format!("Hello {}", operation.source()));
+    }
+
+    fn run(runtime: &mut MockRuntime) {
+        let mut service = Service::<MockRuntime, RawExternalOperation, ()>::new(());
+        service.add_guard(rate_limit).register(greet);
+        Application::new(runtime).service(service).run();
+    }
+
+    fn count_greetings(runtime: &MockRuntime, account: &TestAccount) -> usize {
+        let greeting = format!("Hello {}", account.address());
+        runtime
+            .stdout()
+            .iter()
+            .filter(|line| **line == greeting)
+            .count()
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut runtime = MockRuntime::default();
+        let destination = runtime.rollup_address().unwrap();
+        let alice = TestAccount::new("alice");
+        let bob = TestAccount::new("bob");
+
+        RateLimitConfig {
+            window: 10,
+            quota: 2,
+        }
+        .save(&mut runtime)
+        .unwrap();
+
+        let operations = [
+            alice.sign_operation(&mut runtime, &destination, 1, &[]),
+            alice.sign_operation(&mut runtime, &destination, 2, &[]),
+            alice.sign_operation(&mut runtime, &destination, 3, &[]),
+            bob.sign_operation(&mut runtime, &destination, 1, &[]),
+        ];
+        runtime.add_input_at_level(12, RawExternalOperation::batch_to_bytes(&operations));
+        run(&mut runtime);
+
+        assert_eq!(count_greetings(&runtime, &alice), 2);
+        assert_eq!(count_greetings(&runtime, &bob), 1);
+        assert!(runtime.stdout().contains(
+            &format!(
+                "[WARN] rate limit: {} reached its quota at level 12\n",
+                alice.address()
+            )
+            .as_str()
+        ));
+
+        // Alice is still limited in the same window
+        let operation = alice.operation(&mut runtime, &destination, 4, &[]);
+        runtime.add_input_at_level(19, operation);
+        run(&mut runtime);
+        assert_eq!(count_greetings(&runtime, &alice), 2);
+
+        // But not in the next one
+        let operation = alice.operation(&mut runtime, &destination, 5, &[]);
+        runtime.add_input_at_level(20, operation);
+        run(&mut runtime);
+        assert_eq!(count_greetings(&runtime, &alice), 3);
+    }
+
+    fn receive<R: Runtime>(rt: &mut R, transfer: Transfer<MichelsonBytes>) {
+        rt.write_debug(&format!("Received from {}", transfer.sender()));
+    }
+
+    #[test]
+    fn test_rate_limit_transfers() {
+        let mut runtime = MockRuntime::default();
+        let kt1 = "KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ";
+        let other = "KT1BRhcRAdLia3XQT1mPSofHyrmYpRddgj3s";
+
+        RateLimitConfig {
+            window: 10,
+            quota: 2,
+        }
+        .save(&mut runtime)
+        .unwrap();
+
+        for _ in 0..3 {
+            runtime.add_input(transfer_message(kt1, MichelsonBytes(vec![1])));
+        }
+        runtime.add_input(transfer_message(other, MichelsonBytes(vec![1])));
+
+        let mut service = Service::<MockRuntime, Transfer<MichelsonBytes>, ()>::new(());
+        service.add_guard(rate_limit).register(receive);
+        Application::new(&mut runtime).service(service).run();
+
+        // The transfers are counted per contract
+        let count = |runtime: &MockRuntime, sender: &str| {
+            let received = format!("Received from {}", sender);
+            runtime
+                .stdout()
+                .iter()
+                .filter(|line| **line == received)
+                .count()
+        };
+        assert_eq!(count(&runtime, kt1), 2);
+        assert_eq!(count(&runtime, other), 1);
+    }
+}
@@ -0,0 +1,364 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    core::Runtime,
+    encoding::b58::{self, B58Check, B58Error},
+    plugins::{
+        database::{Bincode, Database, Json},
+        events::Event,
+    },
+};
+
+/// Queue of the levels having receipts, from the oldest one
+///
+/// The levels are saved under `/receipt-levels/<position>`, between the head and the tail of the queue
+const RECEIPT_LEVELS_HEAD_PATH: &str = "/receipt-levels/head";
+const RECEIPT_LEVELS_TAIL_PATH: &str = "/receipt-levels/tail";
+
+/// Hash of an operation, the blake2b 256 bits of its binary encoding
+///
+/// Encoded in base58 with the `o` prefix, like the tezos operations
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OperationHash([u8; 32]);
+
+impl From<[u8; 32]> for OperationHash {
+    fn from(hash: [u8; 32]) -> Self {
+        OperationHash(hash)
+    }
+}
+
+impl B58Check for OperationHash {
+    fn to_b58check(&self) -> String {
+        b58::encode(&b58::OPERATION_HASH, &self.0)
+    }
+
+    fn from_b58check(encoded: &str) -> Result<Self, B58Error> {
+        let hash = b58::decode(&b58::OPERATION_HASH, encoded)?;
+        hash.try_into()
+            .map(OperationHash)
+            .map_err(|_| B58Error::InvalidPayload)
+    }
+}
+
+impl std::fmt::Display for OperationHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_b58check())
+    }
+}
+
+impl Serialize for OperationHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_b58check())
+    }
+}
+
+impl<'de> Deserialize<'de> for OperationHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        OperationHash::from_b58check(&encoded).map_err(D::Error::custom)
+    }
+}
+
+/// Reason why an operation has been rejected before being processed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rejection {
+    /// The operation targets another rollup
+    WrongDestination,
+    /// The level of the operation is greater than its valid_until_level
+    Expired,
+    /// The nonce has already been used or is not accepted yet
+    InvalidNonce,
+    /// The public key is not one of the keys of the account
+    UnauthorizedKey,
+    /// The signature does not match the public key
+    InvalidSignature,
+    /// The nonce of the sponsor has already been used or is not accepted yet
+    InvalidSponsorNonce,
+    /// The public key of the sponsor is not one of the keys of its account
+    UnauthorizedSponsorKey,
+    /// The signature of the sponsor does not match its public key
+    InvalidSponsorSignature,
+    /// The max fee is lower than the base fee
+    FeeTooLow,
+    /// The fee payer cannot pay the max fee
+    InsufficientBalance,
+    /// The durable storage could not be read or written
+    StorageError,
+}
+
+/// Status of a processed operation
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    /// The transitions have been executed
+    Applied,
+    /// A guard of the service did not accept the operation
+    Skipped,
+    /// A transition could not extract its parameters from the operation or returned an error, or the gas limit is exceeded
+    ///
+    /// The state is not rolled back: the changes made by the transitions before failing are kept
+    Failed,
+    /// The operation has not been verified, its nonce is not consumed
+    Rejected(Rejection),
+}
+
+/// Receipt of an operation, written under `/receipts/<operation hash>`
+///
+/// It is encoded in json, so it can be read by polling the durable storage of the rollup node
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub status: Status,
+    /// Level of the inbox containing the operation
+    pub level: u32,
+    /// Fee paid by the fee payer
+    pub fee: u64,
+    /// Events emitted by the transitions
+    pub events: Vec<Event>,
+}
+
+fn receipt_path(hash: &OperationHash) -> String {
+    format!("/receipts/{}", hash)
+}
+
+fn receipt_index_path(level: u32) -> String {
+    format!("/receipt-index/{}", level)
+}
+
+/// Path of the number of receipts of a level
+fn receipt_count_path(level: u32) -> String {
+    format!("/receipt-index/{}/count", level)
+}
+
+/// Path of the hash of the nth receipt of a level
+fn receipt_entry_path(level: u32, index: u64) -> String {
+    format!("/receipt-index/{}/{}", level, index)
+}
+
+fn receipt_level_path(position: u64) -> String {
+    format!("/receipt-levels/{}", position)
+}
+
+fn read_counter<R: Runtime>(runtime: &mut R, path: &str) -> Result<u64, ()> {
+    let counter = <R as Database<Bincode>>::get(runtime, path)?;
+    Ok(counter.unwrap_or_default())
+}
+
+/// Adds the receipt to the index of its level
+///
+/// Each hash is saved under its own path, so the index of a level can grow without rewriting it
+fn index_receipt<R: Runtime>(runtime: &mut R, level: u32, hash: &OperationHash) -> Result<(), ()> {
+    let head = read_counter(runtime, RECEIPT_LEVELS_HEAD_PATH)?;
+    let tail = read_counter(runtime, RECEIPT_LEVELS_TAIL_PATH)?;
+    let last_level = match tail > head {
+        true => <R as Database<Bincode>>::get::<u32>(runtime, &receipt_level_path(tail - 1))?,
+        false => None,
+    };
+    if last_level != Some(level) {
+        <R as Database<Bincode>>::save(runtime, &receipt_level_path(tail), &level)?;
+        <R as Database<Bincode>>::save(runtime, RECEIPT_LEVELS_TAIL_PATH, &(tail + 1))?;
+    }
+
+    let count = read_counter(runtime, &receipt_count_path(level))?;
+    <R as Database<Bincode>>::save(runtime, &receipt_entry_path(level, count), hash)?;
+    <R as Database<Bincode>>::save(runtime, &receipt_count_path(level), &(count + 1)).map(|_| ())
+}
+
+/// Removes the receipts older than `retention` levels
+fn prune_receipts<R: Runtime>(runtime: &mut R, level: u32, retention: u32) -> Result<(), ()> {
+    let mut head = read_counter(runtime, RECEIPT_LEVELS_HEAD_PATH)?;
+    let tail = read_counter(runtime, RECEIPT_LEVELS_TAIL_PATH)?;
+    while head < tail {
+        let oldest =
+            <R as Database<Bincode>>::get::<u32>(runtime, &receipt_level_path(head))?.ok_or(())?;
+        if oldest.saturating_add(retention) > level {
+            break;
+        }
+
+        let count = read_counter(runtime, &receipt_count_path(oldest))?;
+        for index in 0..count {
+            let hash = <R as Database<Bincode>>::get::<OperationHash>(
+                runtime,
+                &receipt_entry_path(oldest, index),
+            )?;
+            if let Some(hash) = hash {
+                runtime.store_delete(&receipt_path(&hash))?;
+            }
+        }
+        runtime.store_delete(&receipt_index_path(oldest))?;
+        runtime.store_delete(&receipt_level_path(head))?;
+        head += 1;
+    }
+    <R as Database<Bincode>>::save(runtime, RECEIPT_LEVELS_HEAD_PATH, &head).map(|_| ())
+}
+
+pub trait Receipts {
+    /// Reads the receipt of an operation
+    fn receipt(&mut self, hash: &OperationHash) -> Result<Option<Receipt>, ()>;
+
+    /// Saves the receipt of an operation
+    ///
+    /// The receipts older than `retention` levels are removed, they are kept forever with a retention of 0.
+    /// Without retention, the receipts are not indexed by level.
+    /// A rejected operation cannot replace the receipt of a processed one, so it is not possible to hide an operation by replaying it.
+    fn save_receipt(
+        &mut self,
+        hash: &OperationHash,
+        receipt: &Receipt,
+        retention: u32,
+    ) -> Result<(), ()>;
+}
+
+impl<R> Receipts for R
+where
+    R: Runtime,
+{
+    fn receipt(&mut self, hash: &OperationHash) -> Result<Option<Receipt>, ()> {
+        <R as Database<Json>>::get(self, &receipt_path(hash))
+    }
+
+    fn save_receipt(
+        &mut self,
+        hash: &OperationHash,
+        receipt: &Receipt,
+        retention: u32,
+    ) -> Result<(), ()> {
+        // The nonce of a processed operation is consumed, its receipt is final
+        let previous = self.receipt(hash)?;
+        if let (Status::Rejected(_), Some(previous)) = (&receipt.status, &previous) {
+            if !matches!(previous.status, Status::Rejected(_)) {
+                return Ok(());
+            }
+        }
+
+        if retention > 0 {
+            prune_receipts(self, receipt.level, retention)?;
+            // A receipt replacing one of the same level is already indexed
+            let is_indexed = matches!(&previous, Some(previous) if previous.level == receipt.level);
+            if !is_indexed {
+                index_receipt(self, receipt.level, hash)?;
+            }
+        }
+
+        <R as Database<Json>>::save(self, &receipt_path(hash), receipt).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OperationHash, Receipt, Receipts, Rejection, Status};
+    use crate::core::{MockRuntime, Runtime};
+    use crate::encoding::b58::B58Check;
+
+    fn receipt(status: Status, level: u32) -> Receipt {
+        Receipt {
+            status,
+            level,
+            fee: 0,
+            events: Vec::default(),
+        }
+    }
+
+    #[test]
+    fn test_operation_hash() {
+        let hash = OperationHash::from([0; 32]);
+        let encoded = hash.to_b58check();
+        assert!(encoded.starts_with('o'));
+        assert_eq!(OperationHash::from_b58check(&encoded), Ok(hash));
+    }
+
+    #[test]
+    fn test_json_receipt() {
+        let mut runtime = MockRuntime::default();
+        let hash = OperationHash::from([1; 32]);
+        let rejected = receipt(Status::Rejected(Rejection::Expired), 5);
+
+        runtime.save_receipt(&hash, &rejected, 0).unwrap();
+        assert_eq!(runtime.receipt(&hash), Ok(Some(rejected)));
+
+        let bytes = runtime
+            .store_read(&format!("/receipts/{}", hash), 8, 1024)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "{\"status\":{\"Rejected\":\"Expired\"},\"level\":5,\"fee\":0,\"events\":[]}"
+        );
+    }
+
+    #[test]
+    fn test_rejection_does_not_replace_receipt() {
+        let mut runtime = MockRuntime::default();
+        let hash = OperationHash::from([1; 32]);
+        let applied = receipt(Status::Applied, 5);
+
+        runtime.save_receipt(&hash, &applied, 0).unwrap();
+        runtime
+            .save_receipt(
+                &hash,
+                &receipt(Status::Rejected(Rejection::InvalidNonce), 6),
+                0,
+            )
+            .unwrap();
+        assert_eq!(runtime.receipt(&hash), Ok(Some(applied)));
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut runtime = MockRuntime::default();
+        let hashes = (0..4)
+            .map(|i| OperationHash::from([i; 32]))
+            .collect::<Vec<_>>();
+
+        runtime
+            .save_receipt(&hashes[0], &receipt(Status::Applied, 1), 10)
+            .unwrap();
+        runtime
+            .save_receipt(&hashes[1], &receipt(Status::Applied, 1), 10)
+            .unwrap();
+        runtime
+            .save_receipt(&hashes[2], &receipt(Status::Applied, 5), 10)
+            .unwrap();
+        runtime
+            .save_receipt(&hashes[3], &receipt(Status::Applied, 11), 10)
+            .unwrap();
+
+        assert_eq!(runtime.receipt(&hashes[0]), Ok(None));
+        assert_eq!(runtime.receipt(&hashes[1]), Ok(None));
+        assert!(runtime.receipt(&hashes[2]).unwrap().is_some());
+        assert!(runtime.receipt(&hashes[3]).unwrap().is_some());
+        assert!(!runtime.store_is_present("/receipt-index/1"));
+    }
+
+    #[test]
+    fn test_no_index_without_retention() {
+        let mut runtime = MockRuntime::default();
+        let hash = OperationHash::from([1; 32]);
+
+        runtime
+            .save_receipt(&hash, &receipt(Status::Applied, 5), 0)
+            .unwrap();
+        assert!(runtime.receipt(&hash).unwrap().is_some());
+        assert!(!runtime.store_is_present("/receipt-index"));
+        assert!(!runtime.store_is_present("/receipt-levels"));
+    }
+
+    #[test]
+    fn test_many_receipts() {
+        let mut runtime = MockRuntime::default();
+        let hashes = (0..200)
+            .map(|i| OperationHash::from([i; 32]))
+            .collect::<Vec<_>>();
+
+        for hash in &hashes {
+            runtime
+                .save_receipt(hash, &receipt(Status::Applied, 1), 10)
+                .unwrap();
+        }
+        runtime
+            .save_receipt(&hashes[0], &receipt(Status::Applied, 11), 10)
+            .unwrap();
+
+        // The receipts of the level 1 are removed, then the first one is saved again at the level 11
+        assert_eq!(runtime.receipt(&hashes[0]).unwrap().unwrap().level, 11);
+        assert!(runtime.receipt(&hashes[1]).unwrap().is_none());
+        assert!(runtime.receipt(&hashes[199]).unwrap().is_none());
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    core::{FromInput, Input, IntoService, Runtime, Service},
+    encoding::{
+        address::ContractAddress,
+        b58::{B58Check, B58Error},
+    },
     plugins::{
         dac::{Dac, PreimageHash},
         installer::Installer,
@@ -12,55 +15,39 @@ use super::internal::{Internal, Transfer};
 
 #[derive(Clone)]
 pub struct TicketUpgrade {
-    from_contract: String,
+    from_contract: ContractAddress,
 }
 
+#[rock_n_rollup::service]
 impl TicketUpgrade {
-    pub fn new(addr: &str) -> Self {
-        Self {
-            from_contract: addr.to_string(),
-        }
+    /// Creates the service accepting upgrades from the given KT1 contract
+    ///
+    /// Returns an error if the address is not a valid contract address
+    pub fn new(addr: &str) -> Result<Self, B58Error> {
+        Ok(Self {
+            from_contract: ContractAddress::from_b58check(addr)?,
+        })
     }
-}
 
-impl FromInput<Vec<u8>, TicketUpgrade> for TicketUpgrade {
-    fn from_input<R: Runtime>(
-        _: &mut R,
-        _: &Input<Vec<u8>>,
-        state: &TicketUpgrade,
-    ) -> Result<Self, ()> {
-        Ok(state.clone())
-    }
-}
-
-fn upgrade_on_ticket<R: Logger + Dac + Installer>(
-    rt: &mut R,
-    transfer: Internal<Transfer<Ticket<MichelsonBytes>>>,
-    state: TicketUpgrade,
-) {
-    let transfer = transfer.payload();
-    if &state.from_contract == transfer.sender() {
-        rt.info("Trying to upgrade");
-        let ticket = transfer.payload();
-        let MichelsonBytes(data) = ticket.contents();
-        let root_hash = PreimageHash::try_from(data).unwrap();
-        let new_kernel = rt.read_from_dac(&root_hash).unwrap();
-        rt.install(&new_kernel).unwrap();
-        // match new_kernel {
-        //     Err(_) => rt.err("Error when deserializing message from the DAC"),
-        //     Ok(new_kernel) => rt.install(new_kernel),
-        // }
-    }
-}
-
-impl<R> IntoService<R, Vec<u8>, TicketUpgrade> for TicketUpgrade
-where
-    R: Runtime,
-{
-    fn into_service(self) -> Service<R, Vec<u8>, Self> {
-        let mut service = Service::<R, Vec<u8>, Self>::new(self);
-        service.register(upgrade_on_ticket);
-        service
+    #[transition]
+    fn upgrade_on_ticket<R: Logger + Dac + Installer>(
+        rt: &mut R,
+        transfer: Internal<Transfer<Ticket<MichelsonBytes>>>,
+        state: TicketUpgrade,
+    ) {
+        let transfer = transfer.payload();
+        if &state.from_contract == transfer.sender() {
+            rt.info("Trying to upgrade");
+            let ticket = transfer.payload();
+            let MichelsonBytes(data) = ticket.contents();
+            let root_hash = PreimageHash::try_from(data).unwrap();
+            let new_kernel = rt.read_from_dac(&root_hash).unwrap();
+            rt.install(&new_kernel).unwrap();
+            // match new_kernel {
+            //     Err(_) => rt.err("Error when deserializing message from the DAC"),
+            //     Ok(new_kernel) => rt.install(new_kernel),
+            // }
+        }
     }
 }
 
@@ -76,7 +63,13 @@ mod tests {
         let mut application = Application::new(&mut runtime);
 
         application
-            .service(TicketUpgrade::new("KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ"))
+            .service(TicketUpgrade::new("KT1CM7YzV9PaKX8u7HKM7vgey3Bbn6ZpZvWZ").unwrap())
             .run();
     }
+
+    #[test]
+    fn test_invalid_contract() {
+        assert!(TicketUpgrade::new("KT1...").is_err());
+        assert!(TicketUpgrade::new("tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb").is_err());
+    }
 }